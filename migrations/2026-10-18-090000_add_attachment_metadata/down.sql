@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE attachment
+DROP COLUMN content_type;
+
+ALTER TABLE attachment
+DROP COLUMN size;
+
+ALTER TABLE attachment
+DROP COLUMN filename;
\ No newline at end of file
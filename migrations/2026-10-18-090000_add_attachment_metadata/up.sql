@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE attachment
+ADD COLUMN filename VARCHAR(250) NOT NULL DEFAULT '';
+
+ALTER TABLE attachment
+ADD COLUMN size INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE attachment
+ADD COLUMN content_type VARCHAR(250);
+
+-- Existing attachments get the filename from the path of their link, their size stays unknown (0)
+UPDATE attachment
+SET filename = CASE
+    WHEN instr(link, '?') > 0 THEN substr(link, 1, instr(link, '?') - 1)
+    ELSE link
+END;
+
+UPDATE attachment
+SET filename = replace(filename, rtrim(filename, replace(filename, '/', '')), '');
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE
+    IF NOT EXISTS variant (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        content TEXT NOT NULL,
+        weight INTEGER NOT NULL DEFAULT 1,
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE
+    )
\ No newline at end of file
@@ -0,0 +1,30 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE
+    macro_old (
+        id INTEGER PRIMARY KEY NOT NULL,
+        name VARCHAR(32) NOT NULL UNIQUE,
+        description VARCHAR(250) NOT NULL,
+        channel_id VARCHAR(32) NOT NULL,
+        message_id VARCHAR(32) NOT NULL,
+        content TEXT NOT NULL
+    );
+
+-- Macros with the same name in multiple guilds cannot be kept apart anymore, keep the oldest one
+INSERT OR IGNORE INTO
+    macro_old (id, name, description, channel_id, message_id, content)
+SELECT
+    id,
+    name,
+    description,
+    channel_id,
+    message_id,
+    content
+FROM
+    macro
+ORDER BY
+    id;
+
+DROP TABLE macro;
+
+ALTER TABLE macro_old
+RENAME TO macro;
\ No newline at end of file
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- SQLite cannot change the unique constraint of an existing table, so the table is rebuilt. Existing
+-- macros are left without a guild, and are assigned to the default guild on startup.
+CREATE TABLE
+    macro_new (
+        id INTEGER PRIMARY KEY NOT NULL,
+        guild_id VARCHAR(32) NOT NULL,
+        name VARCHAR(32) NOT NULL,
+        description VARCHAR(250) NOT NULL,
+        channel_id VARCHAR(32) NOT NULL,
+        message_id VARCHAR(32) NOT NULL,
+        content TEXT NOT NULL,
+        UNIQUE (guild_id, name)
+    );
+
+INSERT INTO
+    macro_new (id, guild_id, name, description, channel_id, message_id, content)
+SELECT
+    id,
+    '',
+    name,
+    description,
+    channel_id,
+    message_id,
+    content
+FROM
+    macro;
+
+DROP TABLE macro;
+
+ALTER TABLE macro_new
+RENAME TO macro;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE
+    IF NOT EXISTS alias (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        name VARCHAR(32) NOT NULL,
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE
+    )
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX invocation_macro_timestamp;
+
+DROP TABLE invocation;
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE
+    IF NOT EXISTS invocation (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        user_id VARCHAR(32) NOT NULL,
+        channel_id VARCHAR(32) NOT NULL,
+        timestamp BIGINT NOT NULL,
+        source VARCHAR(16) NOT NULL,
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE
+    );
+
+CREATE INDEX IF NOT EXISTS invocation_macro_timestamp ON invocation (macro_id, timestamp);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE revision_attachment;
+
+DROP TABLE macro_revision;
\ No newline at end of file
@@ -0,0 +1,53 @@
+-- Your SQL goes here
+CREATE TABLE
+    IF NOT EXISTS macro_revision (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        revision INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        channel_id VARCHAR(32) NOT NULL,
+        message_id VARCHAR(32) NOT NULL,
+        content TEXT NOT NULL,
+        edited_by VARCHAR(32),
+        edited_at BIGINT,
+        UNIQUE (macro_id, revision),
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE
+    );
+
+CREATE TABLE
+    IF NOT EXISTS revision_attachment (
+        id INTEGER PRIMARY KEY NOT NULL,
+        revision_id INTEGER NOT NULL,
+        link TEXT NOT NULL,
+        filename TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        content_type TEXT,
+        FOREIGN KEY (revision_id) REFERENCES macro_revision (id) ON DELETE CASCADE
+    );
+
+-- The current version of existing macros becomes their first revision, its editor is unknown
+INSERT INTO
+    macro_revision (macro_id, revision, description, channel_id, message_id, content)
+SELECT
+    id,
+    1,
+    description,
+    channel_id,
+    message_id,
+    content
+FROM
+    macro;
+
+INSERT INTO
+    revision_attachment (revision_id, link, filename, size, content_type)
+SELECT
+    macro_revision.id,
+    attachment.link,
+    attachment.filename,
+    attachment.size,
+    attachment.content_type
+FROM
+    attachment
+    INNER JOIN macro_revision ON macro_revision.macro_id = attachment.macro_id
+ORDER BY
+    attachment.id;
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE macro
+ADD COLUMN deleted_at BIGINT;
\ No newline at end of file
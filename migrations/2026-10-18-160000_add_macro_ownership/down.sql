@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE macro
+DROP COLUMN updated_at;
+
+ALTER TABLE macro
+DROP COLUMN updated_by;
+
+ALTER TABLE macro
+DROP COLUMN created_at;
+
+ALTER TABLE macro
+DROP COLUMN created_by;
\ No newline at end of file
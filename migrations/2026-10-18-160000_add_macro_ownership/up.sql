@@ -0,0 +1,47 @@
+-- Your SQL goes here
+ALTER TABLE macro
+ADD COLUMN created_by VARCHAR(32);
+
+ALTER TABLE macro
+ADD COLUMN created_at BIGINT;
+
+ALTER TABLE macro
+ADD COLUMN updated_by VARCHAR(32);
+
+ALTER TABLE macro
+ADD COLUMN updated_at BIGINT;
+
+-- Take what is known from the revision history, which only goes back to when it was introduced
+UPDATE macro
+SET
+    created_by = first.edited_by,
+    created_at = first.edited_at
+FROM
+    macro_revision AS first
+WHERE
+    first.macro_id = macro.id
+    AND first.revision = (
+        SELECT
+            MIN(revision)
+        FROM
+            macro_revision
+        WHERE
+            macro_id = macro.id
+    );
+
+UPDATE macro
+SET
+    updated_by = latest.edited_by,
+    updated_at = latest.edited_at
+FROM
+    macro_revision AS latest
+WHERE
+    latest.macro_id = macro.id
+    AND latest.revision = (
+        SELECT
+            MAX(revision)
+        FROM
+            macro_revision
+        WHERE
+            macro_id = macro.id
+    );
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE
+    IF NOT EXISTS tag (
+        id INTEGER PRIMARY KEY NOT NULL,
+        guild_id VARCHAR(32) NOT NULL,
+        name VARCHAR(32) NOT NULL,
+        UNIQUE (guild_id, name)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS macro_tag (
+        macro_id INTEGER NOT NULL,
+        tag_id INTEGER NOT NULL,
+        PRIMARY KEY (macro_id, tag_id),
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES tag (id) ON DELETE CASCADE
+    );
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER macro_search_update;
+
+DROP TRIGGER macro_search_delete;
+
+DROP TRIGGER macro_search_insert;
+
+DROP TABLE macro_search;
\ No newline at end of file
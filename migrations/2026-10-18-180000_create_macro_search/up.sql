@@ -0,0 +1,46 @@
+-- Your SQL goes here
+CREATE VIRTUAL TABLE IF NOT EXISTS macro_search USING fts5 (
+    name,
+    description,
+    content,
+    content = 'macro',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER IF NOT EXISTS macro_search_insert AFTER INSERT ON macro BEGIN
+INSERT INTO
+    macro_search (rowid, name, description, content)
+VALUES
+    (new.id, new.name, new.description, new.content);
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS macro_search_delete AFTER DELETE ON macro BEGIN
+INSERT INTO
+    macro_search (macro_search, rowid, name, description, content)
+VALUES
+    ('delete', old.id, old.name, old.description, old.content);
+
+END;
+
+CREATE TRIGGER IF NOT EXISTS macro_search_update AFTER
+UPDATE OF name,
+description,
+content ON macro BEGIN
+INSERT INTO
+    macro_search (macro_search, rowid, name, description, content)
+VALUES
+    ('delete', old.id, old.name, old.description, old.content);
+
+INSERT INTO
+    macro_search (rowid, name, description, content)
+VALUES
+    (new.id, new.name, new.description, new.content);
+
+END;
+
+-- Index the macros that already exist
+INSERT INTO
+    macro_search (macro_search)
+VALUES
+    ('rebuild');
\ No newline at end of file
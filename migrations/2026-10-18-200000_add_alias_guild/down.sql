@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE
+    alias_old (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        name VARCHAR(32) NOT NULL,
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE
+    );
+
+INSERT INTO
+    alias_old (id, macro_id, name)
+SELECT
+    id,
+    macro_id,
+    name
+FROM
+    alias;
+
+DROP TABLE alias;
+
+ALTER TABLE alias_old
+RENAME TO alias;
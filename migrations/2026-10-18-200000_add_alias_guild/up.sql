@@ -0,0 +1,30 @@
+-- Your SQL goes here
+-- SQLite cannot add a unique constraint to an existing table, so the table is rebuilt
+CREATE TABLE
+    alias_new (
+        id INTEGER PRIMARY KEY NOT NULL,
+        macro_id INTEGER NOT NULL,
+        guild_id VARCHAR(32) NOT NULL,
+        name VARCHAR(32) NOT NULL,
+        FOREIGN KEY (macro_id) REFERENCES macro (id) ON DELETE CASCADE,
+        UNIQUE (guild_id, name)
+    );
+
+-- Names that are used by more than one alias in a guild are kept by the oldest of them
+INSERT OR IGNORE INTO
+    alias_new (id, macro_id, guild_id, name)
+SELECT
+    alias.id,
+    alias.macro_id,
+    macro.guild_id,
+    alias.name
+FROM
+    alias
+    INNER JOIN macro ON macro.id = alias.macro_id
+ORDER BY
+    alias.id;
+
+DROP TABLE alias;
+
+ALTER TABLE alias_new
+RENAME TO alias;
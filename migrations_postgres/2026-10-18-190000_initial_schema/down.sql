@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE macro_tag;
+
+DROP TABLE tag;
+
+DROP TABLE revision_attachment;
+
+DROP TABLE macro_revision;
+
+DROP TABLE invocation;
+
+DROP TABLE alias;
+
+DROP TABLE variant;
+
+DROP TABLE attachment;
+
+DROP TABLE macro;
\ No newline at end of file
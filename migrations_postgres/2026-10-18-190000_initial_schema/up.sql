@@ -0,0 +1,95 @@
+-- Your SQL goes here
+CREATE TABLE macro (
+    id SERIAL PRIMARY KEY,
+    guild_id VARCHAR(32) NOT NULL,
+    name VARCHAR(32) NOT NULL,
+    description TEXT NOT NULL,
+    channel_id VARCHAR(32) NOT NULL,
+    message_id VARCHAR(32) NOT NULL,
+    content TEXT NOT NULL,
+    deleted_at BIGINT,
+    created_by VARCHAR(32),
+    created_at BIGINT,
+    updated_by VARCHAR(32),
+    updated_at BIGINT,
+    -- Matches in the name weigh more than matches in the description, which weigh more than
+    -- matches in the content
+    search TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', name), 'A') ||
+        setweight(to_tsvector('simple', description), 'B') ||
+        setweight(to_tsvector('simple', content), 'C')
+    ) STORED,
+    UNIQUE (guild_id, name)
+);
+
+CREATE INDEX macro_search ON macro USING GIN (search);
+
+CREATE TABLE attachment (
+    id SERIAL PRIMARY KEY,
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    link TEXT NOT NULL,
+    filename TEXT NOT NULL DEFAULT '',
+    size INTEGER NOT NULL DEFAULT 0,
+    content_type TEXT
+);
+
+CREATE TABLE variant (
+    id SERIAL PRIMARY KEY,
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    weight INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE TABLE alias (
+    id SERIAL PRIMARY KEY,
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    guild_id VARCHAR(32) NOT NULL,
+    name VARCHAR(32) NOT NULL,
+    UNIQUE (guild_id, name)
+);
+
+CREATE TABLE invocation (
+    id SERIAL PRIMARY KEY,
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    user_id VARCHAR(32) NOT NULL,
+    channel_id VARCHAR(32) NOT NULL,
+    timestamp BIGINT NOT NULL,
+    source VARCHAR(16) NOT NULL
+);
+
+CREATE INDEX invocation_macro_timestamp ON invocation (macro_id, timestamp);
+
+CREATE TABLE macro_revision (
+    id SERIAL PRIMARY KEY,
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    channel_id VARCHAR(32) NOT NULL,
+    message_id VARCHAR(32) NOT NULL,
+    content TEXT NOT NULL,
+    edited_by VARCHAR(32),
+    edited_at BIGINT,
+    UNIQUE (macro_id, revision)
+);
+
+CREATE TABLE revision_attachment (
+    id SERIAL PRIMARY KEY,
+    revision_id INTEGER NOT NULL REFERENCES macro_revision (id) ON DELETE CASCADE,
+    link TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    content_type TEXT
+);
+
+CREATE TABLE tag (
+    id SERIAL PRIMARY KEY,
+    guild_id VARCHAR(32) NOT NULL,
+    name VARCHAR(32) NOT NULL,
+    UNIQUE (guild_id, name)
+);
+
+CREATE TABLE macro_tag (
+    macro_id INTEGER NOT NULL REFERENCES macro (id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tag (id) ON DELETE CASCADE,
+    PRIMARY KEY (macro_id, tag_id)
+);
\ No newline at end of file
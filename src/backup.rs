@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use poise::serenity_prelude::Timestamp;
+
+use crate::database::Database;
+
+const PREFIX: &str = "macros-";
+const EXTENSION: &str = ".sqlite3";
+
+/// Write a timestamped backup of the database to `dir`, removing the oldest backups so that at
+/// most `retention` remain
+pub async fn create_backup(database: &Database, dir: &Path, retention: usize) -> Result<PathBuf> {
+    let dir = dir.to_owned();
+
+    // Milliseconds keep backups that are made within the same second apart
+    let path = dir.join(format!(
+        "{PREFIX}{}{EXTENSION}",
+        Timestamp::now().format("%Y%m%d-%H%M%S-%3f")
+    ));
+
+    // File system calls block, so they run on the blocking thread pool like database queries
+    let created = dir.clone();
+    tokio::task::spawn_blocking(move || std::fs::create_dir_all(created)).await??;
+
+    database.backup(&path).await?;
+    tokio::task::spawn_blocking(move || prune_backups(&dir, retention)).await??;
+
+    Ok(path)
+}
+
+/// Remove the oldest backups in `dir`, the timestamps in their names sort chronologically
+fn prune_backups(dir: &Path, retention: usize) -> Result<()> {
+    let mut backups = vec![];
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_backup = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(PREFIX) && name.ends_with(EXTENSION));
+
+        if is_backup {
+            backups.push(path);
+        }
+    }
+
+    backups.sort();
+
+    for path in &backups[..backups.len().saturating_sub(retention)] {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
@@ -1,11 +1,20 @@
-use crate::{params::ParameterizedString, Context};
-use anyhow::{anyhow, Result};
-use log::info;
+use crate::{
+    database::models::{AttachmentInfo, NewMacro},
+    params::ParameterizedString,
+    render::{load_includes, Renderer, Variables},
+    split::{split_message, MESSAGE_LIMIT},
+    Context,
+};
+use anyhow::Result;
+use log::{error, info};
 use poise::{
-    serenity_prelude::{self as serenity, CreateEmbed},
+    serenity_prelude::{self as serenity, CreateEmbed, Mentionable, PremiumTier, Timestamp},
     CreateReply,
 };
 
+/// The maximum amount of files in a Discord message
+const MAX_UPLOADS: usize = 10;
+
 #[derive(Debug, poise::Modal)]
 #[name = "Create new macro"]
 struct NewMacroModal {
@@ -14,10 +23,14 @@ struct NewMacroModal {
 
     #[name = "Macro description"]
     description: String,
+
+    #[name = "Tags"]
+    #[placeholder = "setup, troubleshooting (blank keeps the current tags, none removes them)"]
+    tags: Option<String>,
 }
 
 /// Create a new macro
-#[poise::command(context_menu_command = "Create macro")]
+#[poise::command(context_menu_command = "Create macro", guild_only)]
 pub async fn add_macro(
     ctx: Context<'_>,
     #[description = "Message source to base the macro on"] msg: serenity::Message,
@@ -27,70 +40,38 @@ pub async fn add_macro(
     let content = msg.content;
     let attachments = msg.attachments;
 
-    // Check content for formatting errors
-    let pstring = match ParameterizedString::new(&content).and_then(|pstring| {
-        if pstring.parameters() > attachments.len() {
-            Err(anyhow!("Macro contains more parameters than attachments"))
-        } else {
-            Ok(pstring)
-        }
-    }) {
-        Ok(pstring) => pstring,
-        Err(why) => {
-            ctx.send(
-                CreateReply::default()
-                    .embed(
-                        CreateEmbed::new()
-                            .title("Failed to parse macro content")
-                            .description(format!("Your macro contains formatting errors:\n`{why}`"))
-                            .color(0xFC1F28),
-                    )
-                    .ephemeral(true),
-            )
-            .await?;
-
-            return Ok(());
-        }
+    let Some(NewMacroModal {
+        name,
+        description,
+        tags,
+    }) = NewMacroModal::execute(ctx).await?
+    else {
+        return Ok(());
     };
 
-    // Prevent bandwidth abuse by blocking "raw" attachments larger than 10 MiB
-    if attachments[pstring.parameters()..]
-        .iter()
-        .any(|attachment| attachment.size > 1024 * 1024 * 10)
-    {
-        ctx.send(
-            CreateReply::default()
-                .embed(
-                    CreateEmbed::new()
-                        .title("Attachment size exceeds limit")
-                        .description("Attachments that are not embedded as a URL may not exceed 10 MiB in file size")
-                        .color(0xFC1F28),
-                )
-                .ephemeral(true),
-        )
-        .await?;
-
-        return Ok(());
-    }
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let (mut problems, warnings) =
+        validate_macro(ctx, &guild_id, &name, &content, &attachments).await;
 
-    let Some(NewMacroModal { name, description }) = NewMacroModal::execute(ctx).await? else {
-        return Ok(());
+    // Adding a macro again is how it's edited, so leaving the tags blank keeps the current ones
+    // instead of removing them
+    let tags = match tags.as_deref().map(str::trim).unwrap_or_default() {
+        "" => current_tags(ctx, &guild_id, &name).await?,
+        "none" => vec![],
+        tags => parse_tags(tags).into_iter().map(str::to_owned).collect(),
     };
 
-    // Check name for errors
-    if name.len() > 32
-        || name
-            .chars()
-            .any(|c| !c.is_ascii_lowercase() && c != '-' && c != '_')
-    {
+    if !tags.iter().all(|tag| is_valid_name(tag)) {
+        problems.push("Tags must be separated by commas, be lowercase, only contain characters `a-z`, `-` or `_`, and must not exceed 32 characters in length.".to_string());
+    }
+
+    if !problems.is_empty() {
         ctx.send(
             CreateReply::default()
                 .embed(
                     CreateEmbed::new()
-                        .title("Macro name is invalid")
-                        .description(
-                            "Macro name must be lowercase, only contain characters `a-z`, `-` or `_`, and must not exceed 32 characters in length."
-                        )
+                        .title("Failed to create macro")
+                        .description(problems.join("\n\n"))
                         .color(0xFC1F28),
                 )
                 .ephemeral(true),
@@ -101,24 +82,42 @@ pub async fn add_macro(
     }
 
     let attachments = attachments
-        .into_iter()
-        .map(|att| att.url)
+        .iter()
+        .map(AttachmentInfo::from)
         .collect::<Vec<_>>();
 
-    ctx.data.create_macro(
-        &name,
-        &description,
-        &msg.channel_id.to_string(),
-        &msg.id.to_string(),
-        &content,
-        &attachments,
-    )?;
+    let author_id = ctx.author().id.to_string();
+    let now = Timestamp::now().unix_timestamp();
+
+    ctx.data
+        .create_macro(
+            NewMacro {
+                guild_id,
+                name: name.clone(),
+                description,
+                channel_id: msg.channel_id.to_string(),
+                message_id: msg.id.to_string(),
+                content,
+                created_by: Some(author_id.clone()),
+                created_at: Some(now),
+                updated_by: Some(author_id),
+                updated_at: Some(now),
+            },
+            attachments,
+            tags,
+        )
+        .await?;
 
     ctx.send(
         CreateReply::default()
             .embed(
                 CreateEmbed::new()
-                    .description(format!("Successfully created the `.{name}` macro"))
+                    .description(
+                        std::iter::once(format!("Successfully created the `.{name}` macro"))
+                            .chain(warnings)
+                            .collect::<Vec<_>>()
+                            .join("\n\n"),
+                    )
                     .color(0x3BD65D),
             )
             .ephemeral(true),
@@ -129,3 +128,183 @@ pub async fn add_macro(
 
     Ok(())
 }
+
+/// Macro names and aliases must be lowercase, only contain `a-z`, `-` or `_`, and must not exceed
+/// 32 characters
+pub fn is_valid_name(name: &str) -> bool {
+    name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c == '-' || c == '_')
+}
+
+/// Split a comma separated list of tags, ignoring empty and duplicate tags
+fn parse_tags(tags: &str) -> Vec<&str> {
+    let mut result = vec![];
+
+    for tag in tags.split(',').map(str::trim) {
+        if !tag.is_empty() && !result.contains(&tag) {
+            result.push(tag);
+        }
+    }
+
+    result
+}
+
+/// The tags of the macro with the given name, a macro that doesn't exist yet has none
+async fn current_tags(ctx: Context<'_>, guild_id: &str, name: &str) -> Result<Vec<String>> {
+    let Some((r#macro, _)) = ctx.data.get_macro(guild_id, name).await? else {
+        return Ok(vec![]);
+    };
+
+    Ok(ctx
+        .data
+        .get_macro_tags(guild_id)
+        .await?
+        .into_iter()
+        .filter(|(macro_id, _)| *macro_id == r#macro.id)
+        .map(|(_, tag)| tag)
+        .collect())
+}
+
+/// Check a macro against the limits Discord imposes on messages, so that it doesn't fail when it
+/// is invoked
+///
+/// Returns the problems that prevent the macro from being created, and warnings that don't.
+async fn validate_macro(
+    ctx: Context<'_>,
+    guild_id: &str,
+    name: &str,
+    content: &str,
+    attachments: &[serenity::Attachment],
+) -> (Vec<String>, Vec<String>) {
+    let mut problems = vec![];
+    let mut warnings = vec![];
+
+    if !is_valid_name(name) {
+        problems.push("Macro name must be lowercase, only contain characters `a-z`, `-` or `_`, and must not exceed 32 characters in length.".to_string());
+    }
+
+    // Creating the macro would shadow the alias, so it has to be removed first
+    match ctx.data.get_macro(guild_id, name).await {
+        Ok(Some((r#macro, _))) if r#macro.name != name => problems.push(format!(
+            "`.{name}` is already an alias of the `.{}` macro.",
+            r#macro.name
+        )),
+        Ok(_) => {}
+        Err(why) => error!("Failed to look up macro: {why}"),
+    }
+
+    // Check content for formatting errors
+    let pstring = match ParameterizedString::new(content).and_then(|pstring| {
+        pstring.check_attachments(attachments.len())?;
+
+        Ok(pstring)
+    }) {
+        Ok(pstring) => Some(pstring),
+        Err(why) => {
+            problems.push(format!(
+                "Your macro contains formatting errors:\n`{why}`\n```\n{}\n```",
+                why.excerpt(content)
+            ));
+
+            None
+        }
+    };
+
+    if let Some(ref pstring) = pstring {
+        // Code blocks are left untouched, which older macros might not expect
+        if !pstring.raw_attachments().is_empty() {
+            let placeholders = pstring
+                .raw_attachments()
+                .iter()
+                .map(|span| format!("`{}`", &content[span.clone()]))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            warnings.push(format!(
+                "The placeholders {placeholders} are inside a code block and will be sent as-is, move them out of the code block to have them replaced."
+            ));
+        }
+
+        // Render with the argument names standing in for their values
+        let files = attachments[..pstring.attachments()]
+            .iter()
+            .map(AttachmentInfo::from)
+            .collect::<Vec<_>>();
+        let samples = pstring
+            .argument_names()
+            .map(|name| format!("<{name}>"))
+            .collect::<Vec<_>>();
+        let arguments = samples.iter().map(String::as_str).collect::<Vec<_>>();
+        let variables = Variables {
+            author: ctx.author().mention().to_string(),
+            replied: None,
+            channel: ctx.channel_id().mention().to_string(),
+            guild: ctx.guild_id().and_then(|guild_id| guild_id.name(ctx)),
+            timestamp: serenity::Timestamp::now().unix_timestamp(),
+        };
+
+        match load_includes(ctx.data, guild_id, pstring).await {
+            Ok(includes) => match Renderer::new(&files, &arguments, &variables)
+                .includes(&includes, name)
+                .render(pstring)
+            {
+                Ok(rendered) if rendered.chars().count() > MESSAGE_LIMIT => warnings.push(format!(
+                    "The macro is {} characters long and will be split over {} messages.",
+                    rendered.chars().count(),
+                    split_message(&rendered).len()
+                )),
+                Ok(_) => {}
+                Err(why) => problems.push(format!("Your macro cannot be rendered:\n`{why}`")),
+            },
+            Err(why) => error!("Failed to look up included macros: {why}"),
+        }
+    }
+
+    // Attachments that are not embedded as a URL are uploaded with every invocation
+    let uploads = &attachments[pstring.map_or(0, |pstring| pstring.attachments())..];
+
+    if uploads.len() > MAX_UPLOADS {
+        problems.push(format!(
+            "Only {MAX_UPLOADS} attachments that are not embedded as a URL can be uploaded, this macro has {}.",
+            uploads.len()
+        ));
+    }
+
+    // Prevent bandwidth abuse by blocking "raw" attachments larger than 10 MiB
+    if uploads
+        .iter()
+        .any(|attachment| attachment.size > 1024 * 1024 * 10)
+    {
+        problems.push(
+            "Attachments that are not embedded as a URL may not exceed 10 MiB in file size."
+                .to_string(),
+        );
+    }
+
+    let total = uploads
+        .iter()
+        .map(|attachment| attachment.size as u64)
+        .sum::<u64>();
+    let limit = upload_limit(ctx.guild().map(|guild| guild.premium_tier));
+
+    if total > limit {
+        problems.push(format!(
+            "Attachments that are not embedded as a URL may not exceed {} MiB in total in this server, this macro has {:.1} MiB.",
+            limit / 1024 / 1024,
+            total as f64 / 1024.0 / 1024.0
+        ));
+    }
+
+    (problems, warnings)
+}
+
+/// The total size of the files a message may contain, which depends on the server's boost tier
+fn upload_limit(tier: Option<PremiumTier>) -> u64 {
+    match tier {
+        Some(PremiumTier::Tier2) => 1024 * 1024 * 50,
+        Some(PremiumTier::Tier3) => 1024 * 1024 * 100,
+        _ => 1024 * 1024 * 10,
+    }
+}
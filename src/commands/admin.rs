@@ -0,0 +1,93 @@
+use crate::{backup::create_backup, env, Context};
+
+use anyhow::Result;
+use log::{error, info};
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// Manage the bot
+#[poise::command(
+    slash_command,
+    subcommands("backup"),
+    subcommand_required,
+    owners_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn admin(_: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// Manage backups of the database
+#[poise::command(slash_command, subcommands("backup_now"), subcommand_required)]
+pub async fn backup(_: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// Back up the database right away
+#[poise::command(slash_command, rename = "now")]
+pub async fn backup_now(ctx: Context<'_>) -> Result<()> {
+    let Some(ref dir) = *env::BACKUP_DIR else {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description("Backups are disabled, no `BACKUP_DIR` has been configured")
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    if !ctx.data.supports_backups() {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description("Backups are only supported for SQLite databases")
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    match create_backup(ctx.data, dir, *env::BACKUP_RETENTION).await {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description("Failed to back up the database")
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to back up database: {why}");
+        }
+        Ok(path) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "Successfully backed up the database to `{}`",
+                                path.display()
+                            ))
+                            .color(0x3BD65D),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            info!("Backed up database to {}", path.display());
+        }
+    }
+
+    Ok(())
+}
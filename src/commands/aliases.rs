@@ -0,0 +1,266 @@
+use crate::{commands::is_valid_name, Context};
+
+use anyhow::Result;
+use log::{error, info};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+/// Manage alternative names of macros
+#[poise::command(
+    slash_command,
+    subcommands("alias_add", "alias_remove", "alias_list"),
+    subcommand_required
+)]
+pub async fn alias(_: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// Add an alternative name to a macro
+#[poise::command(slash_command, rename = "add")]
+pub async fn alias_add(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+    #[description = "The alternative name"] alias: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    if !is_valid_name(&alias) {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .title("Alias is invalid")
+                        .description("Alias must be lowercase, only contain characters `a-z`, `-` or `_`, and must not exceed 32 characters in length.")
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    // Aliases of aliases refer to the macro itself
+    let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!("No macro with the name `.{name}` exists"))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    if let Some((existing, _)) = ctx.data.get_macro(&guild_id, &alias).await? {
+        let description = if existing.name == alias {
+            format!("A macro with the name `.{alias}` already exists")
+        } else {
+            format!(
+                "`.{alias}` is already an alias of the `.{}` macro",
+                existing.name
+            )
+        };
+
+        ctx.send(
+            CreateReply::default()
+                .embed(CreateEmbed::new().description(description).color(0xFC1F28))
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    match ctx.data.create_alias(r#macro.id, &alias).await {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "Failed to add the `.{alias}` alias to the `.{}` macro",
+                                r#macro.name
+                            ))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to create alias: {why}");
+
+            return Ok(());
+        }
+        // Aliases of macros in the trash keep their names, so that restoring them doesn't clash
+        Ok(None) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "`.{alias}` is already an alias of another macro, which may be in the trash"
+                            ))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            return Ok(());
+        }
+        Ok(Some(_)) => {}
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .description(format!(
+                        "Successfully added the `.{alias}` alias to the `.{}` macro",
+                        r#macro.name
+                    ))
+                    .color(0x3BD65D),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+
+    info!("Alias .{alias} has been added to macro .{}", r#macro.name);
+
+    Ok(())
+}
+
+/// Remove an alternative name from a macro
+#[poise::command(slash_command, rename = "remove")]
+pub async fn alias_remove(
+    ctx: Context<'_>,
+    #[description = "The alias to remove"] alias: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    match ctx.data.delete_alias(&guild_id, &alias).await {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("Failed to remove the `.{alias}` alias"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to delete alias: {why}");
+        }
+        Ok(false) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("No alias with the name `.{alias}` exists"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+        Ok(true) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("Successfully removed the `.{alias}` alias"))
+                            .color(0x3BD65D),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            info!("Removed alias .{alias}");
+        }
+    }
+
+    Ok(())
+}
+
+/// List the aliases of all macros, or of a single macro
+#[poise::command(slash_command, rename = "list")]
+pub async fn alias_list(
+    ctx: Context<'_>,
+    #[description = "Only list the aliases of this macro"] name: Option<String>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    // Resolve the macro in case an alias was given
+    let name = match name {
+        Some(name) => match ctx.data.get_macro(&guild_id, &name).await? {
+            Some((r#macro, _)) => Some(r#macro.name),
+            None => {
+                ctx.send(
+                    CreateReply::default()
+                        .embed(
+                            CreateEmbed::new()
+                                .description(format!("No macro with the name `.{name}` exists"))
+                                .color(0xFC1F28),
+                        )
+                        .ephemeral(true),
+                )
+                .await?;
+
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let aliases = ctx.data.get_aliases(&guild_id).await?;
+
+    // Group the aliases by the macro they refer to, which they are already sorted by
+    let mut groups: Vec<(&str, Vec<String>)> = vec![];
+    for (alias, macro_name) in &aliases {
+        if name.as_ref().is_some_and(|name| name != macro_name) {
+            continue;
+        }
+
+        match groups.last_mut() {
+            Some((last, names)) if last == macro_name => names.push(format!("`.{}`", alias.name)),
+            _ => groups.push((macro_name, vec![format!("`.{}`", alias.name)])),
+        }
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(match name {
+            Some(ref name) => format!("Aliases of .{name}"),
+            None => "Aliases".to_string(),
+        })
+        .color(0x0773D6);
+
+    if groups.is_empty() {
+        embed = embed.description("There are no aliases");
+    }
+
+    // Embeds are limited to 25 fields
+    for (macro_name, names) in groups.iter().take(25) {
+        embed = embed.field(format!(".{macro_name}"), names.join(", "), false);
+    }
+
+    if groups.len() > 25 {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "And {} more",
+            groups.len() - 25
+        )));
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
@@ -1,16 +1,25 @@
-use crate::Context;
+use crate::{env, Context};
 
 use anyhow::Result;
 use log::{error, info};
-use poise::{serenity_prelude::CreateEmbed, CreateReply};
+use poise::{
+    serenity_prelude::{CreateEmbed, Timestamp},
+    CreateReply,
+};
 
-/// Delete a macro
-#[poise::command(slash_command)]
+/// Move a macro to the trash
+#[poise::command(slash_command, guild_only)]
 pub async fn delete(
     ctx: Context<'_>,
     #[description = "The name of the macro to remove"] name: String,
 ) -> Result<()> {
-    match ctx.data.delete_macro(&name) {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    match ctx
+        .data
+        .delete_macro(&guild_id, &name, Timestamp::now().unix_timestamp())
+        .await
+    {
         Err(why) => {
             ctx.send(
                 CreateReply::default()
@@ -42,7 +51,10 @@ pub async fn delete(
                 CreateReply::default()
                     .embed(
                         CreateEmbed::new()
-                            .description(format!("Successfully deleted the `.{name}` macro"))
+                            .description(format!(
+                                "Successfully deleted the `.{name}` macro, it can be restored with `/macro restore` for {} days",
+                                *env::TRASH_RETENTION_DAYS
+                            ))
                             .color(0x3BD65D),
                     )
                     .ephemeral(true),
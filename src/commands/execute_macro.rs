@@ -1,35 +1,104 @@
 use anyhow::Result;
 use log::{error, info};
 use poise::serenity_prelude::{
-    Context, CreateAllowedMentions, CreateAttachment, CreateMessage, Message,
+    Context, CreateAllowedMentions, CreateAttachment, CreateMessage, Mentionable, Message,
 };
+use rand::Rng;
 
 use crate::{
     database::{
-        models::{Attachment, Macro},
-        Database,
+        models::{Attachment, AttachmentInfo, Source, Variant},
+        CachedMacro, Database,
     },
     params::ParameterizedString,
+    render::{load_includes, Renderer, Variables},
+    split::split_message,
 };
 
 pub async fn execute_macro(ctx: &Context, message: &Message, database: &Database) -> Result<()> {
-    let command = &message.content.split(" ").next().unwrap()[1..];
+    let (command, arguments) = message.content[1..]
+        .split_once(char::is_whitespace)
+        .unwrap_or((&message.content[1..], ""));
 
-    let Some((r#macro, attachments)) = database.get_macro(command)? else {
+    // Macros are scoped per guild, so they can't be invoked in direct messages
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+
+    // Most messages starting with a dot aren't macros, the cache turns them away without a query
+    let Some(cached) = database
+        .get_cached_macro(&guild_id.to_string(), command)
+        .await?
+    else {
         return Ok(()); // Ignore if macro doesn't exist
     };
 
-    // Attempt to retrieve up-to-date content and attachments from source
-    let success = match ctx
-        .http
-        .get_message(
-            r#macro.channel_id.parse().unwrap(),
-            r#macro.message_id.parse().unwrap(),
+    let r#macro = &cached.r#macro;
+    let macro_id = r#macro.id;
+
+    // Variants are only stored in the database, so they don't need the source message
+    if let Some(variant) = pick_variant(&cached.variants) {
+        let param_str = ParameterizedString::new(&variant.content)?;
+
+        if execute_macro_with_database(
+            ctx,
+            database,
+            message,
+            command,
+            arguments,
+            &param_str,
+            &cached.attachments,
         )
-        .await
-    {
-        Ok(src_message) => execute_macro_with_message(ctx, src_message, message).await?,
-        Err(_) => execute_macro_with_database(ctx, message, r#macro, attachments).await?,
+        .await?
+        {
+            info!(
+                "Executed macro .{command} variant #{} (by {})",
+                variant.id,
+                message.author.display_name()
+            );
+
+            record_invocation(database, message, macro_id, Source::Database).await;
+        }
+
+        return Ok(());
+    }
+
+    // Attempt to retrieve up-to-date content and attachments from source, macros whose IDs don't
+    // refer to a message use the copy in the database
+    let src_message = match (r#macro.channel_id.parse(), r#macro.message_id.parse()) {
+        (Ok(channel_id), Ok(message_id)) => ctx.http.get_message(channel_id, message_id).await.ok(),
+        _ => None,
+    };
+
+    let (success, source) = match src_message {
+        Some(src_message) => {
+            let success = execute_macro_with_message(
+                ctx,
+                database,
+                &cached,
+                src_message,
+                message,
+                command,
+                arguments,
+            )
+            .await?;
+
+            (success, Source::Live)
+        }
+        None => {
+            let success = execute_macro_with_database(
+                ctx,
+                database,
+                message,
+                command,
+                arguments,
+                cached.template()?,
+                &cached.attachments,
+            )
+            .await?;
+
+            (success, Source::Database)
+        }
     };
 
     if success {
@@ -37,20 +106,49 @@ pub async fn execute_macro(ctx: &Context, message: &Message, database: &Database
             "Executed macro .{command} (by {})",
             message.author.display_name()
         );
+
+        record_invocation(database, message, macro_id, source).await;
     }
 
     Ok(())
 }
 
+/// Keep track of how often macros are used, failing to do so doesn't affect the invocation
+async fn record_invocation(database: &Database, message: &Message, macro_id: i32, source: Source) {
+    if let Err(why) = database
+        .record_invocation(
+            macro_id,
+            &message.author.id.to_string(),
+            &message.channel_id.to_string(),
+            message.timestamp.unix_timestamp(),
+            source,
+        )
+        .await
+    {
+        error!("Failed to record macro invocation: {why}");
+    }
+}
+
 async fn execute_macro_with_message(
     ctx: &Context,
+    database: &Database,
+    cached: &CachedMacro,
     src_message: Message,
     message: &Message,
+    command: &str,
+    arguments: &str,
 ) -> Result<bool> {
-    // Build macro content, replacing params with our files
-    let param_str = ParameterizedString::new(&src_message.content)?;
+    // Build macro content, replacing params with our files, the source message has usually not
+    // been edited since the macro was cached
+    let parsed;
+    let param_str = if src_message.content == cached.r#macro.content {
+        cached.template()?
+    } else {
+        parsed = ParameterizedString::new(&src_message.content)?;
+        &parsed
+    };
 
-    if src_message.attachments.len() < param_str.parameters() {
+    if src_message.attachments.len() < param_str.attachments() {
         error!("Message has too many parameters for the attachments it has, cannot send macro!");
 
         message
@@ -64,66 +162,276 @@ async fn execute_macro_with_message(
         return Ok(false);
     }
 
-    let macro_content = param_str.to_string(
-        src_message.attachments[..param_str.parameters()]
-            .iter()
-            .map(|att| &att.url)
-            .collect(),
-    )?;
+    let arguments = param_str.split_arguments(arguments);
+    if !check_arguments(ctx, message, command, param_str, &arguments).await? {
+        return Ok(false);
+    }
+
+    let files = src_message.attachments[..param_str.attachments()]
+        .iter()
+        .map(AttachmentInfo::from)
+        .collect::<Vec<_>>();
 
-    let mut builder = CreateMessage::new().content(&macro_content);
+    let Some(macro_content) = render(
+        ctx, database, message, command, param_str, &files, &arguments,
+    )
+    .await?
+    else {
+        return Ok(false);
+    };
 
     // Any additional files will be attached directly to the message
-    for attachment in &src_message.attachments[param_str.parameters()..] {
-        builder = builder.add_file(CreateAttachment::url(ctx, &attachment.url).await?);
+    let mut uploads = vec![];
+    for attachment in &src_message.attachments[param_str.attachments()..] {
+        uploads.push(CreateAttachment::url(ctx, &attachment.url).await?);
     }
 
-    if let Some(ref reference) = message.referenced_message {
-        builder = builder
-            .reference_message(&**reference)
-            .allowed_mentions(CreateAllowedMentions::new().replied_user(true))
-    }
-
-    message.channel_id.send_message(ctx, builder).await?;
-    message.delete(ctx).await?;
+    send_macro(ctx, message, &macro_content, uploads).await?;
 
     Ok(true)
 }
 
 async fn execute_macro_with_database(
     ctx: &Context,
+    database: &Database,
     message: &Message,
-    r#macro: Macro,
-    attachments: Vec<Attachment>,
+    command: &str,
+    arguments: &str,
+    param_str: &ParameterizedString<'_>,
+    attachments: &[Attachment],
 ) -> Result<bool> {
-    // Build macro content, replacing params with our files
-    let param_str = ParameterizedString::new(&r#macro.content)?;
-    let mut macro_content = param_str.to_string(
-        attachments[..param_str.parameters()]
-            .iter()
-            .map(|att| &att.link)
-            .collect(),
-    )?;
+    // Variants are only checked against the attachments of the macro when they are added
+    if let Err(why) = param_str.check_attachments(attachments.len()) {
+        error!("Failed to execute macro .{command}: {why}");
+
+        message
+            .channel_id
+            .send_message(
+                ctx,
+                CreateMessage::new().content(format!("Macro invocation failed: {why}")),
+            )
+            .await?;
+
+        return Ok(false);
+    }
+
+    let arguments = param_str.split_arguments(arguments);
+    if !check_arguments(ctx, message, command, param_str, &arguments).await? {
+        return Ok(false);
+    }
+
+    let files = attachments[..param_str.attachments()]
+        .iter()
+        .map(Attachment::info)
+        .collect::<Vec<_>>();
+
+    let Some(mut macro_content) = render(
+        ctx, database, message, command, param_str, &files, &arguments,
+    )
+    .await?
+    else {
+        return Ok(false);
+    };
 
     // Any additional files will be attached at the end of the message, to fix issues with expiring urls
-    if !attachments[param_str.parameters()..].is_empty() {
+    if !attachments[param_str.attachments()..].is_empty() {
         macro_content += "\n";
 
-        for attachment in &attachments[param_str.parameters()..] {
+        for attachment in &attachments[param_str.attachments()..] {
             macro_content += &format!("\n{}", attachment.link);
         }
     }
 
-    let mut builder = CreateMessage::new().content(&macro_content);
+    send_macro(ctx, message, &macro_content, vec![]).await?;
+
+    Ok(true)
+}
+
+/// Send the rendered macro in place of the invoking message
+///
+/// Content that doesn't fit in a single message is split up, in which case only the first message
+/// replies to the message the invoker replied to, and files are uploaded with the last message.
+async fn send_macro(
+    ctx: &Context,
+    message: &Message,
+    content: &str,
+    mut uploads: Vec<CreateAttachment>,
+) -> Result<()> {
+    let parts = split_message(content);
+    let last = parts.len() - 1;
+
+    for (index, part) in parts.into_iter().enumerate() {
+        // Only users can be pinged, so that arguments can't mention @everyone or roles
+        let mut mentions = CreateAllowedMentions::new().all_users(true);
+        let mut builder = CreateMessage::new().content(part);
 
-    if let Some(ref reference) = message.referenced_message {
-        builder = builder
-            .reference_message(&**reference)
-            .allowed_mentions(CreateAllowedMentions::new().replied_user(true))
+        if let Some(ref reference) = message.referenced_message {
+            if index == 0 {
+                builder = builder.reference_message(&**reference);
+                mentions = mentions.replied_user(true);
+            }
+        }
+
+        builder = builder.allowed_mentions(mentions);
+
+        if index == last {
+            builder = builder.files(std::mem::take(&mut uploads));
+        }
+
+        message.channel_id.send_message(ctx, builder).await?;
     }
 
-    message.channel_id.send_message(ctx, builder).await?;
     message.delete(ctx).await?;
 
-    Ok(true)
+    Ok(())
+}
+
+/// Pick between the macro's own content, which has a weight of 1, and its variants
+///
+/// Returns `None` if the macro's own content was picked.
+fn pick_variant(variants: &[Variant]) -> Option<&Variant> {
+    if variants.is_empty() {
+        return None;
+    }
+
+    // Summed as u64, so that weights that were stored before they were limited can't overflow
+    let total = 1 + variants
+        .iter()
+        .map(|variant| variant.weight.max(0) as u64)
+        .sum::<u64>();
+
+    let mut roll = rand::thread_rng().gen_range(0..total);
+    if roll == 0 {
+        return None;
+    }
+
+    roll -= 1;
+    for variant in variants {
+        let weight = variant.weight.max(0) as u64;
+        if roll < weight {
+            return Some(variant);
+        }
+
+        roll -= weight;
+    }
+
+    None
+}
+
+/// Render the macro content, letting the invoker know if that isn't possible
+async fn render(
+    ctx: &Context,
+    database: &Database,
+    message: &Message,
+    command: &str,
+    param_str: &ParameterizedString<'_>,
+    files: &[AttachmentInfo],
+    arguments: &[&str],
+) -> Result<Option<String>> {
+    let variables = variables(ctx, message);
+    let guild_id = message.guild_id.unwrap_or_default().to_string();
+    let includes = load_includes(database, &guild_id, param_str).await?;
+
+    match Renderer::new(files, arguments, &variables)
+        .includes(&includes, command)
+        .render(param_str)
+    {
+        Ok(content) => Ok(Some(content)),
+        Err(why) => {
+            error!("Failed to render macro .{command}: {why}");
+
+            message
+                .channel_id
+                .send_message(
+                    ctx,
+                    CreateMessage::new().content(format!("Macro invocation failed: {why}")),
+                )
+                .await?;
+
+            Ok(None)
+        }
+    }
+}
+
+/// Collect the values for the built-in placeholders from the invoking message
+fn variables(ctx: &Context, message: &Message) -> Variables {
+    Variables {
+        author: message.author.mention().to_string(),
+        replied: message
+            .referenced_message
+            .as_ref()
+            .map(|reference| reference.author.mention().to_string()),
+        channel: message.channel_id.mention().to_string(),
+        guild: message.guild_id.and_then(|guild_id| guild_id.name(ctx)),
+        timestamp: message.timestamp.unix_timestamp(),
+    }
+}
+
+/// Let the invoker know which arguments are missing, returns `false` if the macro cannot be sent
+async fn check_arguments(
+    ctx: &Context,
+    message: &Message,
+    command: &str,
+    param_str: &ParameterizedString<'_>,
+    arguments: &[&str],
+) -> Result<bool> {
+    let Err(why) = param_str.check_arguments(arguments) else {
+        return Ok(true);
+    };
+
+    message
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new().content(format!(
+                "Macro invocation failed: {why}\nUsage: `.{command}{}`",
+                param_str.usage()
+            )),
+        )
+        .await?;
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(id: i32, weight: i32) -> Variant {
+        Variant {
+            id,
+            macro_id: 1,
+            content: format!("Variant {id}"),
+            weight,
+        }
+    }
+
+    #[test]
+    fn pick_without_variants() {
+        assert_eq!(pick_variant(&[]), None);
+    }
+
+    #[test]
+    fn pick_skips_variants_without_weight() {
+        let variants = [variant(1, 0), variant(2, -5), variant(3, 1000)];
+
+        for _ in 0..100 {
+            if let Some(picked) = pick_variant(&variants) {
+                assert_eq!(picked.id, 3);
+            }
+        }
+    }
+
+    #[test]
+    fn pick_with_huge_weights() {
+        let variants = [
+            variant(1, i32::MAX),
+            variant(2, i32::MAX),
+            variant(3, i32::MAX),
+        ];
+
+        for _ in 0..100 {
+            assert!(pick_variant(&variants).is_some_and(|picked| (1..=3).contains(&picked.id)));
+        }
+    }
 }
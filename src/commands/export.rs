@@ -0,0 +1,358 @@
+use std::{collections::HashSet, num::NonZeroU64};
+
+use crate::{
+    commands::is_valid_name,
+    database::models::{AttachmentInfo, NewMacro},
+    filter::Filter,
+    params::ParameterizedString,
+    Context,
+};
+
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use poise::{
+    serenity_prelude::{self as serenity, CreateAttachment, CreateEmbed, Timestamp},
+    CreateReply,
+};
+use serde::{Deserialize, Serialize};
+
+/// The version of the export format, increased whenever it changes in a way older versions of the
+/// bot can't read
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Export {
+    version: u32,
+    macros: Vec<ExportedMacro>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedMacro {
+    name: String,
+    description: String,
+    content: String,
+    channel_id: String,
+    message_id: String,
+    #[serde(default)]
+    attachments: Vec<ExportedAttachment>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedAttachment {
+    link: String,
+    filename: String,
+    size: i32,
+    content_type: Option<String>,
+}
+
+impl ExportedAttachment {
+    fn info(&self) -> AttachmentInfo {
+        AttachmentInfo {
+            link: self.link.clone(),
+            filename: self.filename.clone(),
+            size: self.size,
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
+/// What happens to macros that already exist when importing
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum ImportStrategy {
+    #[name = "Merge: overwrite existing macros"]
+    Merge,
+    #[name = "Replace: overwrite existing macros and delete the others"]
+    Replace,
+    #[name = "Skip existing macros"]
+    SkipExisting,
+}
+
+/// Download all macros as a file
+#[poise::command(slash_command)]
+pub async fn export(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let tags = ctx.data.get_macro_tags(&guild_id).await?;
+
+    let mut macros = vec![];
+    for (r#macro, attachments) in ctx.data.get_macros_with_attachments(&guild_id).await? {
+        macros.push(ExportedMacro {
+            attachments: attachments
+                .into_iter()
+                .map(|attachment| ExportedAttachment {
+                    link: attachment.link,
+                    filename: attachment.filename,
+                    size: attachment.size,
+                    content_type: attachment.content_type,
+                })
+                .collect(),
+            tags: tags
+                .iter()
+                .filter(|(macro_id, _)| *macro_id == r#macro.id)
+                .map(|(_, tag)| tag.clone())
+                .collect(),
+            name: r#macro.name,
+            description: r#macro.description,
+            content: r#macro.content,
+            channel_id: r#macro.channel_id,
+            message_id: r#macro.message_id,
+        });
+    }
+
+    let count = macros.len();
+    let export = serde_json::to_vec_pretty(&Export {
+        version: EXPORT_VERSION,
+        macros,
+    })?;
+
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .description(format!("Exported {count} macro(s)"))
+                    .color(0x3BD65D),
+            )
+            .attachment(CreateAttachment::bytes(export, "macros.json"))
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Import macros from a file created by `/macro export`
+#[poise::command(slash_command)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "The exported macros"] file: serenity::Attachment,
+    #[description = "What to do with macros that already exist"] strategy: ImportStrategy,
+    #[description = "Only show what would change"] dry_run: Option<bool>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let dry_run = dry_run.unwrap_or_default();
+
+    let export = match read_export(&file).await {
+        Ok(export) => export,
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Failed to read the export file")
+                            .description(format!("`{why}`"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            return Ok(());
+        }
+    };
+
+    let existing = ctx
+        .data
+        .get_macros(&guild_id)
+        .await?
+        .into_iter()
+        .map(|r#macro| r#macro.name)
+        .collect::<HashSet<_>>();
+
+    let mut created = vec![];
+    let mut updated = vec![];
+    let mut skipped = vec![];
+    let mut invalid = vec![];
+    let mut seen = HashSet::new();
+
+    for r#macro in &export.macros {
+        let name = r#macro.name.as_str();
+
+        if !seen.insert(name) || !is_valid(r#macro) {
+            invalid.push(name);
+            continue;
+        }
+
+        // Importing the macro would shadow an alias
+        if !existing.contains(name) && ctx.data.get_macro(&guild_id, name).await?.is_some() {
+            invalid.push(name);
+            continue;
+        }
+
+        match (existing.contains(name), strategy) {
+            (false, _) => created.push(r#macro),
+            (true, ImportStrategy::SkipExisting) => skipped.push(name),
+            (true, _) => updated.push(r#macro),
+        }
+    }
+
+    let deleted = match strategy {
+        ImportStrategy::Replace => existing
+            .iter()
+            .filter(|name| !seen.contains(name.as_str()))
+            .map(String::as_str)
+            .collect(),
+        _ => vec![],
+    };
+
+    if !dry_run {
+        if let Err(why) = apply_import(ctx, &guild_id, &created, &updated, &deleted).await {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description("Failed to import macros, nothing has been changed")
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to import macros: {why}");
+
+            return Ok(());
+        }
+
+        info!(
+            "Imported macros: {} created, {} updated, {} deleted",
+            created.len(),
+            updated.len(),
+            deleted.len()
+        );
+    }
+
+    let names = |names: &[&str]| {
+        if names.is_empty() {
+            "None".to_string()
+        } else {
+            let list = names
+                .iter()
+                .map(|name| format!("`.{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            // Embed field values are limited to 1024 characters
+            Filter::Truncate(1000).apply(&list)
+        }
+    };
+
+    let created = created
+        .iter()
+        .map(|r#macro| r#macro.name.as_str())
+        .collect::<Vec<_>>();
+    let updated = updated
+        .iter()
+        .map(|r#macro| r#macro.name.as_str())
+        .collect::<Vec<_>>();
+
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .title(if dry_run {
+                        "Import preview, nothing has been changed"
+                    } else {
+                        "Import finished"
+                    })
+                    .field(
+                        format!("Created ({})", created.len()),
+                        names(&created),
+                        false,
+                    )
+                    .field(
+                        format!("Updated ({})", updated.len()),
+                        names(&updated),
+                        false,
+                    )
+                    .field(
+                        format!("Deleted ({})", deleted.len()),
+                        names(&deleted),
+                        false,
+                    )
+                    .field(
+                        format!("Skipped ({})", skipped.len()),
+                        names(&skipped),
+                        false,
+                    )
+                    .field(
+                        format!("Invalid ({})", invalid.len()),
+                        names(&invalid),
+                        false,
+                    )
+                    .color(if dry_run { 0x0773D6 } else { 0x3BD65D }),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn read_export(file: &serenity::Attachment) -> Result<Export> {
+    let export = serde_json::from_slice::<Export>(&file.download().await?)?;
+
+    if export.version > EXPORT_VERSION {
+        return Err(anyhow!(
+            "The file was exported by a newer version of the bot (version {})",
+            export.version
+        ));
+    }
+
+    Ok(export)
+}
+
+/// Imported macros are held to the same rules as macros created from a message
+fn is_valid(r#macro: &ExportedMacro) -> bool {
+    is_valid_name(&r#macro.name)
+        && is_valid_id(&r#macro.channel_id)
+        && is_valid_id(&r#macro.message_id)
+        && r#macro.tags.iter().all(|tag| is_valid_name(tag))
+        && ParameterizedString::new(&r#macro.content)
+            .and_then(|pstring| pstring.check_attachments(r#macro.attachments.len()))
+            .is_ok()
+}
+
+/// Discord IDs are positive integers
+fn is_valid_id(id: &str) -> bool {
+    id.parse::<NonZeroU64>().is_ok()
+}
+
+async fn apply_import(
+    ctx: Context<'_>,
+    guild_id: &str,
+    created: &[&ExportedMacro],
+    updated: &[&ExportedMacro],
+    deleted: &[&str],
+) -> Result<()> {
+    let author_id = ctx.author().id.to_string();
+    let now = Timestamp::now().unix_timestamp();
+
+    let macros = created
+        .iter()
+        .chain(updated)
+        .map(|r#macro| {
+            let new = NewMacro {
+                guild_id: guild_id.to_owned(),
+                name: r#macro.name.clone(),
+                description: r#macro.description.clone(),
+                channel_id: r#macro.channel_id.clone(),
+                message_id: r#macro.message_id.clone(),
+                content: r#macro.content.clone(),
+                created_by: Some(author_id.clone()),
+                created_at: Some(now),
+                updated_by: Some(author_id.clone()),
+                updated_at: Some(now),
+            };
+            let attachments = r#macro
+                .attachments
+                .iter()
+                .map(ExportedAttachment::info)
+                .collect();
+
+            (new, attachments, r#macro.tags.clone())
+        })
+        .collect();
+    let deleted = deleted.iter().map(|name| name.to_string()).collect();
+
+    ctx.data.import_macros(guild_id, macros, deleted, now).await
+}
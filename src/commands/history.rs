@@ -0,0 +1,176 @@
+use crate::{filter::Filter, Context};
+
+use anyhow::Result;
+use log::{error, info};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter, Timestamp},
+    CreateReply,
+};
+
+/// Show the previous versions of a macro
+#[poise::command(slash_command)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!("No macro with the name `.{name}` exists"))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let revisions = ctx.data.get_revisions(r#macro.id).await?;
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("History of .{}", r#macro.name))
+        .color(0x0773D6);
+
+    // Embeds are limited to 25 fields and 6000 characters in total, a field takes up at most
+    // about 190 characters with a preview of 120, which leaves plenty of room for the rest
+    for (index, revision) in revisions.iter().take(25).enumerate() {
+        let editor = match (&revision.edited_by, revision.edited_at) {
+            (Some(user_id), Some(timestamp)) => format!("<@{user_id}>, <t:{timestamp}:R>"),
+            _ => "Unknown editor".to_string(),
+        };
+
+        embed = embed.field(
+            if index == 0 {
+                format!("#{} (current)", revision.revision)
+            } else {
+                format!("#{}", revision.revision)
+            },
+            format!(
+                "{editor}\n{}",
+                Filter::Truncate(120).apply(&revision.content)
+            ),
+            false,
+        );
+    }
+
+    if revisions.len() > 25 {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "And {} older revisions",
+            revisions.len() - 25
+        )));
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Restore a previous version of a macro
+#[poise::command(slash_command)]
+pub async fn rollback(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+    #[description = "The number of the revision to restore"] revision: i32,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!("No macro with the name `.{name}` exists"))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    match ctx
+        .data
+        .rollback_macro(
+            r#macro.id,
+            revision,
+            &ctx.author().id.to_string(),
+            Timestamp::now().unix_timestamp(),
+        )
+        .await
+    {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "Failed to restore revision #{revision} of the `.{}` macro",
+                                r#macro.name
+                            ))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to roll back macro: {why}");
+        }
+        Ok(None) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "The `.{}` macro has no revision #{revision}",
+                                r#macro.name
+                            ))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+        Ok(Some(new)) => {
+            let mut description = format!(
+                "Successfully restored revision #{revision} of the `.{}` macro as revision #{}",
+                r#macro.name, new.revision
+            );
+
+            // Invocations prefer the source message over the copy in the database
+            let src_message = match (r#macro.channel_id.parse(), r#macro.message_id.parse()) {
+                (Ok(channel_id), Ok(message_id)) => {
+                    ctx.http().get_message(channel_id, message_id).await.ok()
+                }
+                _ => None,
+            };
+
+            if let Some(src_message) = src_message {
+                description += &format!(
+                    "\n\nThe macro is still sent with the content of its [source message]({}), edit that message to match the restored revision.",
+                    src_message.link()
+                );
+            }
+
+            ctx.send(
+                CreateReply::default()
+                    .embed(CreateEmbed::new().description(description).color(0x3BD65D))
+                    .ephemeral(true),
+            )
+            .await?;
+
+            info!(
+                "Macro .{} has been rolled back to revision #{revision}",
+                r#macro.name
+            );
+        }
+    }
+
+    Ok(())
+}
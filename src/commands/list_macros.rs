@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use crate::{database::models::Macro, Context};
 
@@ -11,11 +11,21 @@ use poise::{
     CreateReply,
 };
 
-/// List all macros currently available
-#[poise::command(slash_command)]
-pub async fn macros(ctx: Context<'_>) -> Result<()> {
-    let macros = ctx.data.get_macros()?;
-    let pages = macros.chunks(25).len().max(1);
+/// List all macros currently available, grouped by tag
+#[poise::command(slash_command, guild_only)]
+pub async fn macros(
+    ctx: Context<'_>,
+    #[description = "Only list macros with this tag"]
+    #[autocomplete = "autocomplete_tag"]
+    tag: Option<String>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let macros = ctx.data.get_macros(&guild_id).await?;
+    let tags = ctx.data.get_macro_tags(&guild_id).await?;
+
+    let groups = group_macros(&macros, &tags, tag.as_deref());
+    let embeds = paginate(&groups);
+    let pages = embeds.len().max(1);
 
     let ctx_id = ctx.id();
     let prev_button_id = format!("{ctx_id}prev");
@@ -28,7 +38,7 @@ pub async fn macros(ctx: Context<'_>) -> Result<()> {
         ]);
 
         CreateReply::default()
-            .embed(create_macro_embed(&macros, 0))
+            .embed(create_macro_embed(&embeds, 0, tag.as_deref()))
             .components(vec![components])
     };
 
@@ -72,8 +82,11 @@ pub async fn macros(ctx: Context<'_>) -> Result<()> {
             .create_response(
                 ctx.serenity_context,
                 CreateInteractionResponse::UpdateMessage(
-                    CreateInteractionResponseMessage::new()
-                        .embed(create_macro_embed(&macros, page)),
+                    CreateInteractionResponseMessage::new().embed(create_macro_embed(
+                        &embeds,
+                        page,
+                        tag.as_deref(),
+                    )),
                 ),
             )
             .await?;
@@ -82,27 +95,92 @@ pub async fn macros(ctx: Context<'_>) -> Result<()> {
     Ok(())
 }
 
-fn create_macro_embed(macros: &[Macro], page: usize) -> CreateEmbed {
-    let chunks = macros.chunks(25);
+async fn autocomplete_tag(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
 
+    ctx.data
+        .get_tags(&guild_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|tag| tag.starts_with(partial))
+        .take(25)
+        .collect()
+}
+
+/// Group macros by their tags, macros with multiple tags appear in every group and macros without
+/// tags are put in a group of their own
+fn group_macros<'a>(
+    macros: &'a [Macro],
+    tags: &'a [(i32, String)],
+    tag: Option<&str>,
+) -> Vec<(&'a str, Vec<&'a Macro>)> {
+    let mut groups = BTreeMap::<&str, Vec<&Macro>>::new();
+    let mut untagged = vec![];
+
+    for r#macro in macros {
+        let mut macro_tags = tags
+            .iter()
+            .filter(|(macro_id, _)| *macro_id == r#macro.id)
+            .map(|(_, name)| name.as_str())
+            .peekable();
+
+        if macro_tags.peek().is_none() {
+            untagged.push(r#macro);
+        }
+
+        for name in macro_tags {
+            if tag.is_none() || tag == Some(name) {
+                groups.entry(name).or_default().push(r#macro);
+            }
+        }
+    }
+
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    if tag.is_none() && !untagged.is_empty() {
+        groups.push(("other", untagged));
+    }
+
+    groups
+}
+
+/// Split the groups into pages of at most 25 macros, which never contain more than one group
+fn paginate<'a>(groups: &'a [(&'a str, Vec<&'a Macro>)]) -> Vec<(&'a str, &'a [&'a Macro])> {
+    groups
+        .iter()
+        .flat_map(|(name, macros)| macros.chunks(25).map(move |chunk| (*name, chunk)))
+        .collect()
+}
+
+fn create_macro_embed(pages: &[(&str, &[&Macro])], page: usize, tag: Option<&str>) -> CreateEmbed {
     let mut embed = CreateEmbed::new()
         .title("List of macros")
         .footer(CreateEmbedFooter::new(format!(
             "Page {}/{}",
             page + 1,
-            chunks.len().max(1)
+            pages.len().max(1)
         )))
         .color(0x0773D6);
 
-    if macros.is_empty() {
-        embed = embed.description("You haven't created any macros yet");
-    } else {
-        for r#macro in macros
-            .chunks(25)
-            .nth(page)
-            .expect("Page exceeds max page count")
-        {
-            embed = embed.field(&r#macro.name, &r#macro.description, false);
+    match (pages.get(page), tag) {
+        (None, None) => embed = embed.description("You haven't created any macros yet"),
+        (None, Some(tag)) => {
+            embed = embed.description(format!("There are no macros tagged `{tag}`"))
+        }
+        (Some((category, macros)), _) => {
+            embed = embed.title(format!("List of macros: {category}"));
+
+            for r#macro in macros.iter() {
+                let value = match (&r#macro.updated_by, r#macro.updated_at) {
+                    (Some(user_id), Some(timestamp)) => format!(
+                        "{}\n*Last edited by <@{user_id}> <t:{timestamp}:R>*",
+                        r#macro.description
+                    ),
+                    _ => r#macro.description.clone(),
+                };
+
+                embed = embed.field(&r#macro.name, value, false);
+            }
         }
     }
 
@@ -0,0 +1,23 @@
+use crate::{
+    commands::{
+        alias, export, history, import, info, restore, rollback, search, stats, trash, variant,
+    },
+    Context,
+};
+
+use anyhow::Result;
+
+/// Manage macros
+#[poise::command(
+    slash_command,
+    rename = "macro",
+    subcommands(
+        "alias", "export", "history", "import", "info", "restore", "rollback", "search", "stats",
+        "trash", "variant"
+    ),
+    subcommand_required,
+    guild_only
+)]
+pub async fn macro_(_: Context<'_>) -> Result<()> {
+    Ok(())
+}
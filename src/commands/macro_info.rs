@@ -0,0 +1,103 @@
+use crate::Context;
+
+use anyhow::Result;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// Show the details of a macro
+#[poise::command(slash_command)]
+pub async fn info(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    let Some((r#macro, attachments)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!("No macro with the name `.{name}` exists"))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let edit = |user_id: &Option<String>, timestamp: Option<i64>| match (user_id, timestamp) {
+        (Some(user_id), Some(timestamp)) => format!("<@{user_id}>, <t:{timestamp}:f>"),
+        _ => "Unknown".to_string(),
+    };
+
+    let aliases = ctx
+        .data
+        .get_aliases(&guild_id)
+        .await?
+        .into_iter()
+        .filter(|(alias, _)| alias.macro_id == r#macro.id)
+        .map(|(alias, _)| format!("`.{}`", alias.name))
+        .collect::<Vec<_>>();
+
+    let tags = ctx
+        .data
+        .get_macro_tags(&guild_id)
+        .await?
+        .into_iter()
+        .filter(|(macro_id, _)| *macro_id == r#macro.id)
+        .map(|(_, tag)| format!("`{tag}`"))
+        .collect::<Vec<_>>();
+
+    let embed = CreateEmbed::new()
+        .title(format!(".{}", r#macro.name))
+        .description(&r#macro.description)
+        .field(
+            "Created by",
+            edit(&r#macro.created_by, r#macro.created_at),
+            true,
+        )
+        .field(
+            "Last edited by",
+            edit(&r#macro.updated_by, r#macro.updated_at),
+            true,
+        )
+        .field(
+            "Source",
+            format!(
+                "https://discord.com/channels/{guild_id}/{}/{}",
+                r#macro.channel_id, r#macro.message_id
+            ),
+            false,
+        )
+        .field("Attachments", attachments.len().to_string(), true)
+        .field(
+            "Variants",
+            ctx.data.get_variants(r#macro.id).await?.len().to_string(),
+            true,
+        )
+        .field(
+            "Aliases",
+            if aliases.is_empty() {
+                "None".to_string()
+            } else {
+                aliases.join(", ")
+            },
+            true,
+        )
+        .field(
+            "Tags",
+            if tags.is_empty() {
+                "None".to_string()
+            } else {
+                tags.join(", ")
+            },
+            true,
+        )
+        .color(0x0773D6);
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
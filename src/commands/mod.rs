@@ -1,9 +1,29 @@
 mod add_macro;
+mod admin;
+mod aliases;
 mod delete_macro;
 mod execute_macro;
+mod export;
+mod history;
 mod list_macros;
+mod macro_command;
+mod macro_info;
+mod search;
+mod stats;
+mod trash;
+mod variants;
 
 pub use add_macro::*;
+pub use admin::*;
+pub use aliases::*;
 pub use delete_macro::*;
 pub use execute_macro::*;
+pub use export::*;
+pub use history::*;
 pub use list_macros::*;
+pub use macro_command::*;
+pub use macro_info::*;
+pub use search::*;
+pub use stats::*;
+pub use trash::*;
+pub use variants::*;
@@ -0,0 +1,48 @@
+use crate::{filter::Filter, Context};
+
+use anyhow::Result;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+/// How many results a search shows
+const MAX_RESULTS: i64 = 10;
+
+/// Find macros by their name, description or content
+#[poise::command(slash_command)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "Words to look for"] query: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let results = ctx
+        .data
+        .search_macros(&guild_id, &query, MAX_RESULTS)
+        .await?;
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Search results for \"{query}\""))
+        .color(0x0773D6);
+
+    if results.is_empty() {
+        embed = embed.description("No macros match your search");
+    }
+
+    for result in &results {
+        let snippet = if result.snippet.trim().is_empty() {
+            &result.description
+        } else {
+            &result.snippet
+        };
+
+        // Embed field values are limited to 1024 characters
+        embed = embed.field(
+            format!(".{}", result.name),
+            Filter::Truncate(1000).apply(snippet),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
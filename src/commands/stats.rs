@@ -0,0 +1,131 @@
+use crate::Context;
+
+use anyhow::Result;
+use poise::{
+    serenity_prelude::{CreateEmbed, Timestamp},
+    CreateReply,
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// How many entries the rankings show
+const RANKING_SIZE: i64 = 10;
+
+/// Show how often macros are used
+#[poise::command(slash_command)]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Only show the usage of this macro"] name: Option<String>,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let now = Timestamp::now().unix_timestamp();
+
+    let embed = match name {
+        Some(name) => {
+            let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+                ctx.send(
+                    CreateReply::default()
+                        .embed(
+                            CreateEmbed::new()
+                                .description(format!("No macro with the name `.{name}` exists"))
+                                .color(0xFC1F28),
+                        )
+                        .ephemeral(true),
+                )
+                .await?;
+
+                return Ok(());
+            };
+
+            let invokers = ctx
+                .data
+                .get_top_invokers(&guild_id, Some(r#macro.id), now - 30 * DAY, RANKING_SIZE)
+                .await?;
+
+            CreateEmbed::new()
+                .title(format!("Usage of .{}", r#macro.name))
+                .field(
+                    "Last 7 days",
+                    ctx.data
+                        .count_invocations(r#macro.id, now - 7 * DAY)
+                        .await?
+                        .to_string(),
+                    true,
+                )
+                .field(
+                    "Last 30 days",
+                    ctx.data
+                        .count_invocations(r#macro.id, now - 30 * DAY)
+                        .await?
+                        .to_string(),
+                    true,
+                )
+                .field(
+                    "All time",
+                    ctx.data.count_invocations(r#macro.id, 0).await?.to_string(),
+                    true,
+                )
+                .field("Top invokers (30 days)", invoker_ranking(&invokers), false)
+                .color(0x0773D6)
+        }
+        None => {
+            let month = ctx
+                .data
+                .get_top_macros(&guild_id, now - 30 * DAY, RANKING_SIZE)
+                .await?;
+            let week = ctx
+                .data
+                .get_top_macros(&guild_id, now - 7 * DAY, i64::MAX)
+                .await?;
+            let invokers = ctx
+                .data
+                .get_top_invokers(&guild_id, None, now - 30 * DAY, RANKING_SIZE)
+                .await?;
+
+            let macros = if month.is_empty() {
+                "No macros have been used".to_string()
+            } else {
+                month
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, count))| {
+                        let weekly = week
+                            .iter()
+                            .find(|(weekly, _)| weekly == name)
+                            .map_or(0, |(_, count)| *count);
+
+                        format!(
+                            "{}. `.{name}`: {count} ({weekly} in the last 7 days)",
+                            index + 1
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+
+            CreateEmbed::new()
+                .title("Macro usage")
+                .field("Top macros (30 days)", macros, false)
+                .field("Top invokers (30 days)", invoker_ranking(&invokers), false)
+                .color(0x0773D6)
+        }
+    };
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+fn invoker_ranking(invokers: &[(String, i64)]) -> String {
+    if invokers.is_empty() {
+        return "No macros have been used".to_string();
+    }
+
+    invokers
+        .iter()
+        .enumerate()
+        .map(|(index, (user_id, count))| format!("{}. <@{user_id}>: {count}", index + 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,156 @@
+use crate::{env, Context};
+
+use anyhow::Result;
+use log::{error, info};
+use poise::{
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// List the macros that have been deleted
+#[poise::command(slash_command)]
+pub async fn trash(ctx: Context<'_>) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+    let macros = ctx.data.get_deleted_macros(&guild_id).await?;
+
+    let mut embed = CreateEmbed::new().title("Trash").color(0x0773D6);
+
+    if macros.is_empty() {
+        embed = embed.description("There are no deleted macros");
+    }
+
+    // Embeds are limited to 25 fields
+    for r#macro in macros.iter().take(25) {
+        let deleted_at = r#macro.deleted_at.unwrap_or_default();
+
+        embed = embed.field(
+            format!(".{}", r#macro.name),
+            format!(
+                "Deleted <t:{deleted_at}:R>, purged <t:{}:R>",
+                deleted_at.saturating_add(env::TRASH_RETENTION_DAYS.saturating_mul(DAY))
+            ),
+            false,
+        );
+    }
+
+    if macros.len() > 25 {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "And {} more",
+            macros.len() - 25
+        )));
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Restore a deleted macro
+#[poise::command(slash_command)]
+pub async fn restore(
+    ctx: Context<'_>,
+    #[description = "The name of the macro to restore"] name: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    // The name may have been taken by an alias in the meantime
+    if let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!(
+                            "`.{name}` is already an alias of the `.{}` macro",
+                            r#macro.name
+                        ))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    // Macros may have been created with the names of its aliases in the meantime
+    let mut taken = vec![];
+    if let Some(r#macro) = ctx
+        .data
+        .get_deleted_macros(&guild_id)
+        .await?
+        .into_iter()
+        .find(|r#macro| r#macro.name == name)
+    {
+        for alias in ctx.data.get_macro_aliases(r#macro.id).await? {
+            if ctx.data.get_macro(&guild_id, &alias.name).await?.is_some() {
+                taken.push(format!("`.{}`", alias.name));
+            }
+        }
+    }
+
+    if !taken.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .embed(
+                    CreateEmbed::new()
+                        .description(format!(
+                            "The aliases {} of the `.{name}` macro are now used by other macros, remove them with `/alias remove` first",
+                            taken.join(", ")
+                        ))
+                        .color(0xFC1F28),
+                )
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    match ctx.data.restore_macro(&guild_id, &name).await {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("Failed to restore the `.{name}` macro"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to restore macro: {why}");
+        }
+        Ok(false) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("No deleted macro with the name `.{name}` exists"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+        Ok(true) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("Successfully restored the `.{name}` macro"))
+                            .color(0x3BD65D),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            info!("Restored macro .{name}");
+        }
+    }
+
+    Ok(())
+}
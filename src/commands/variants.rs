@@ -0,0 +1,262 @@
+use crate::{error::TemplateError, filter::Filter, params::ParameterizedString, Context};
+
+use anyhow::Result;
+use log::{error, info};
+use poise::{
+    serenity_prelude::{self as serenity, CreateEmbed, CreateEmbedFooter},
+    CreateReply,
+};
+
+/// The highest weight a variant can have, relative to the weight of 1 of the macro's own content
+const MAX_WEIGHT: i32 = 1000;
+
+#[derive(Debug, poise::Modal)]
+#[name = "Add macro variant"]
+struct NewVariantModal {
+    #[name = "Macro name"]
+    name: String,
+
+    #[name = "Weight"]
+    #[placeholder = "1"]
+    weight: Option<String>,
+}
+
+/// Add an alternative body to an existing macro
+///
+/// The variant uses the attachments of the macro it belongs to, files on the message are ignored.
+#[poise::command(context_menu_command = "Add macro variant", guild_only)]
+pub async fn add_variant(
+    ctx: Context<'_>,
+    #[description = "Message source to base the variant on"] msg: serenity::Message,
+) -> Result<()> {
+    use poise::Modal as _;
+
+    let content = msg.content;
+
+    if let Err(why) = ParameterizedString::new(&content) {
+        ctx.send(parse_failure(&why, &content)).await?;
+
+        return Ok(());
+    }
+
+    let Some(NewVariantModal { name, weight }) = NewVariantModal::execute(ctx).await? else {
+        return Ok(());
+    };
+
+    let weight = match weight.as_deref().map(str::trim).unwrap_or_default() {
+        "" => 1,
+        weight => match weight.parse::<i32>() {
+            Ok(weight) if (1..=MAX_WEIGHT).contains(&weight) => weight,
+            _ => {
+                ctx.send(
+                    CreateReply::default()
+                        .embed(
+                            CreateEmbed::new()
+                                .title("Variant weight is invalid")
+                                .description(format!(
+                                    "Variant weight must be a whole number from 1 to {MAX_WEIGHT}."
+                                ))
+                                .color(0xFC1F28),
+                        )
+                        .ephemeral(true),
+                )
+                .await?;
+
+                return Ok(());
+            }
+        },
+    };
+
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    // Variants are added to the macro itself when an alias is given
+    let Some((r#macro, attachments)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(missing_macro(&name)).await?;
+
+        return Ok(());
+    };
+
+    // The variant can only use as many attachments as the macro has
+    if let Err(why) = ParameterizedString::new(&content)
+        .and_then(|pstring| pstring.check_attachments(attachments.len()))
+    {
+        ctx.send(parse_failure(&why, &content)).await?;
+
+        return Ok(());
+    }
+
+    // The macro may have been deleted in the meantime
+    let Some(variant) = ctx
+        .data
+        .create_variant(r#macro.id, &content, weight)
+        .await?
+    else {
+        ctx.send(missing_macro(&name)).await?;
+
+        return Ok(());
+    };
+
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .description(format!(
+                        "Successfully added variant #{} to the `.{}` macro",
+                        variant.id, r#macro.name
+                    ))
+                    .color(0x3BD65D),
+            )
+            .ephemeral(true),
+    )
+    .await?;
+
+    info!(
+        "Variant #{} has been added to macro .{}",
+        variant.id, r#macro.name
+    );
+
+    Ok(())
+}
+
+fn missing_macro(name: &str) -> CreateReply {
+    CreateReply::default()
+        .embed(
+            CreateEmbed::new()
+                .description(format!("No macro with the name `.{name}` exists"))
+                .color(0xFC1F28),
+        )
+        .ephemeral(true)
+}
+
+fn parse_failure(why: &TemplateError, content: &str) -> CreateReply {
+    CreateReply::default()
+        .embed(
+            CreateEmbed::new()
+                .title("Failed to parse variant content")
+                .description(format!(
+                    "Your variant contains formatting errors:\n`{why}`\n```\n{}\n```",
+                    why.excerpt(content)
+                ))
+                .color(0xFC1F28),
+        )
+        .ephemeral(true)
+}
+
+/// Manage the alternative bodies of a macro
+#[poise::command(
+    slash_command,
+    subcommands("variant_list", "variant_remove"),
+    subcommand_required
+)]
+pub async fn variant(_: Context<'_>) -> Result<()> {
+    Ok(())
+}
+
+/// List the variants of a macro
+#[poise::command(slash_command, rename = "list")]
+pub async fn variant_list(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(missing_macro(&name)).await?;
+
+        return Ok(());
+    };
+
+    let variants = ctx.data.get_variants(r#macro.id).await?;
+    let preview = |content: &str| Filter::Truncate(200).apply(content);
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Variants of .{name}"))
+        .field("Original (weight 1)", preview(&r#macro.content), false)
+        .color(0x0773D6);
+
+    // Embeds are limited to 25 fields, one of which is taken by the original content
+    for variant in variants.iter().take(24) {
+        embed = embed.field(
+            format!("#{} (weight {})", variant.id, variant.weight),
+            preview(&variant.content),
+            false,
+        );
+    }
+
+    if variants.len() > 24 {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "And {} more",
+            variants.len() - 24
+        )));
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a variant from a macro
+#[poise::command(slash_command, rename = "remove")]
+pub async fn variant_remove(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"] name: String,
+    #[description = "The number of the variant to remove"] id: i32,
+) -> Result<()> {
+    let guild_id = ctx.guild_id().unwrap_or_default().to_string();
+
+    let Some((r#macro, _)) = ctx.data.get_macro(&guild_id, &name).await? else {
+        ctx.send(missing_macro(&name)).await?;
+
+        return Ok(());
+    };
+
+    match ctx.data.delete_variant(r#macro.id, id).await {
+        Err(why) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "Failed to remove variant #{id} from the `.{name}` macro"
+                            ))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            error!("Failed to delete variant: {why}");
+        }
+        Ok(false) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!("The `.{name}` macro has no variant #{id}"))
+                            .color(0xFC1F28),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+        }
+        Ok(true) => {
+            ctx.send(
+                CreateReply::default()
+                    .embed(
+                        CreateEmbed::new()
+                            .description(format!(
+                                "Successfully removed variant #{id} from the `.{name}` macro"
+                            ))
+                            .color(0x3BD65D),
+                    )
+                    .ephemeral(true),
+            )
+            .await?;
+
+            info!("Removed variant #{id} from macro .{name}");
+        }
+    }
+
+    Ok(())
+}
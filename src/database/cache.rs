@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use self_cell::self_cell;
+
+use super::models::{Attachment, Macro, Variant};
+use crate::{error::TemplateError, params::ParameterizedString};
+
+self_cell!(
+    /// The content of a macro along with the template parsed from it
+    struct Template {
+        owner: String,
+
+        #[covariant]
+        dependent: ParameterizedString,
+    }
+);
+
+/// Everything that is needed to invoke a macro, kept in memory so that invocations don't have to
+/// wait for the database
+pub struct CachedMacro {
+    pub r#macro: Macro,
+    pub attachments: Vec<Attachment>,
+    pub variants: Vec<Variant>,
+    template: Result<Template, TemplateError>,
+}
+
+impl CachedMacro {
+    pub fn new(r#macro: Macro, attachments: Vec<Attachment>, variants: Vec<Variant>) -> Self {
+        let template = Template::try_new(r#macro.content.clone(), |content| {
+            ParameterizedString::new(content)
+        });
+
+        Self {
+            r#macro,
+            attachments,
+            variants,
+            template,
+        }
+    }
+
+    /// The parsed content of the macro
+    pub fn template(&self) -> Result<&ParameterizedString<'_>, TemplateError> {
+        match self.template {
+            Ok(ref template) => Ok(template.borrow_dependent()),
+            Err(ref why) => Err(why.clone()),
+        }
+    }
+}
+
+/// The macros of a guild by their names and the names of their aliases
+pub type GuildMacros = HashMap<String, Arc<CachedMacro>>;
+
+/// The macros of every guild that has been loaded since the last change to its macros
+#[derive(Default)]
+pub struct MacroCache {
+    guilds: RwLock<HashMap<String, Arc<GuildMacros>>>,
+    /// Increased on every change, so that macros which were loaded before a change aren't stored
+    generation: AtomicU64,
+}
+
+impl MacroCache {
+    pub fn get(&self, guild_id: &str) -> Option<Arc<GuildMacros>> {
+        self.guilds.read().unwrap().get(guild_id).cloned()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Store the macros of a guild, unless they were loaded before the generation changed
+    pub fn insert(
+        &self,
+        guild_id: String,
+        macros: GuildMacros,
+        generation: u64,
+    ) -> Arc<GuildMacros> {
+        let macros = Arc::new(macros);
+        let mut guilds = self.guilds.write().unwrap();
+
+        if self.generation() == generation {
+            guilds.insert(guild_id, macros.clone());
+        }
+
+        macros
+    }
+
+    /// Forget the macros of a guild, they are loaded again when they are next needed
+    pub fn invalidate(&self, guild_id: &str) {
+        let mut guilds = self.guilds.write().unwrap();
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        guilds.remove(guild_id);
+    }
+
+    /// Forget the macros of every guild, for changes that aren't tied to a guild
+    pub fn clear(&self) {
+        let mut guilds = self.guilds.write().unwrap();
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        guilds.clear();
+    }
+}
@@ -1,124 +1,1144 @@
+mod cache;
 pub mod models;
 pub mod schema;
 
+use std::{path::Path, sync::Arc};
+
 use anyhow::{anyhow, Result};
+#[cfg(feature = "postgres")]
+use diesel::PgConnection;
 use diesel::{
     connection::SimpleConnection,
-    r2d2::{ConnectionManager, Pool},
-    BelongingToDsl, Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
-    SelectableHelper, SqliteConnection,
+    dsl::count_star,
+    r2d2::{self, ConnectionManager, CustomizeConnection, Pool},
+    BelongingToDsl, Connection, ExpressionMethods, GroupedBy, OptionalExtension, QueryDsl,
+    QueryResult, RunQueryDsl, SelectableHelper, SqliteConnection,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use models::{Attachment, Macro, NewAttachment, NewMacro};
-use schema::{attachment, macro_};
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+use crate::params::ParameterizedString;
+use cache::{GuildMacros, MacroCache};
+use models::{
+    Alias, Attachment, AttachmentInfo, Macro, NewAlias, NewAttachment, NewInvocation, NewMacro,
+    NewMacroTag, NewRevision, NewRevisionAttachment, NewTag, NewVariant, Revision,
+    RevisionAttachment, SearchResult, Source, Tag, Variant,
+};
+use schema::{
+    alias, attachment, invocation, macro_, macro_revision, macro_tag, revision_attachment, tag,
+    variant,
+};
+
+pub use cache::CachedMacro;
+
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
+/// Run `$body` on a blocking thread with a connection from the pool of whichever backend is in
+/// use, so that queries don't hold up the async runtime
+///
+/// The body is compiled once per backend, so queries only need to be written once as long as
+/// every backend supports them. It is moved to another thread, so it can only capture owned
+/// values.
+macro_rules! with_connection {
+    ($database:expr, |$conn:ident| $body:block) => {{
+        let pool = $database.pool.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<_> {
+            match &pool {
+                DatabasePool::Sqlite(pool) => {
+                    let $conn = &mut pool.get()?;
+                    $body
+                }
+                #[cfg(feature = "postgres")]
+                DatabasePool::Postgres(pool) => {
+                    let $conn = &mut pool.get()?;
+                    $body
+                }
+            }
+        })
+        .await?
+    }};
+}
+
+/// Foreign keys are enabled per connection, so every pooled connection needs to turn them on
+#[derive(Debug)]
+struct ForeignKeys;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ForeignKeys {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> std::result::Result<(), r2d2::Error> {
+        conn.batch_execute("PRAGMA foreign_keys = ON")
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+#[derive(Clone)]
+enum DatabasePool {
+    Sqlite(Pool<ConnectionManager<SqliteConnection>>),
+    #[cfg(feature = "postgres")]
+    Postgres(Pool<ConnectionManager<PgConnection>>),
+}
 
 #[derive(Clone)]
 pub struct Database {
-    pool: Pool<ConnectionManager<SqliteConnection>>,
+    pool: DatabasePool,
+    /// Macros that can be invoked, every change to the macros of a guild invalidates its entries
+    cache: Arc<MacroCache>,
 }
 
 impl Database {
+    /// Connect to the database at `url` and run pending migrations
+    ///
+    /// `postgres://` and `postgresql://` URLs connect to PostgreSQL when the `postgres` feature is
+    /// enabled, anything else is opened as an SQLite database.
     pub fn connect(url: impl AsRef<str>) -> Result<Database> {
-        let manager = ConnectionManager::<SqliteConnection>::new(url.as_ref());
-        let pool = Pool::builder().build(manager)?;
+        let url = url.as_ref();
 
-        let conn = &mut pool.get()?;
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Self::connect_postgres(url);
+        }
 
-        conn.batch_execute("PRAGMA foreign_keys = ON")?;
-        conn.run_pending_migrations(MIGRATIONS)
+        // Migrations run without foreign keys, so that tables can be rebuilt without cascading
+        let conn = &mut SqliteConnection::establish(url)?;
+
+        conn.run_pending_migrations(SQLITE_MIGRATIONS)
             .map_err(|why| anyhow!("Migration failed: {why}"))?;
 
-        Ok(Self { pool })
+        let manager = ConnectionManager::<SqliteConnection>::new(url);
+        let pool = Pool::builder()
+            .connection_customizer(Box::new(ForeignKeys))
+            .build(manager)?;
+
+        Ok(Self {
+            pool: DatabasePool::Sqlite(pool),
+            cache: Arc::default(),
+        })
     }
 
-    pub fn get_macros(&self) -> Result<Vec<Macro>> {
-        let mut conn = self.pool.get()?;
+    #[cfg(feature = "postgres")]
+    fn connect_postgres(url: &str) -> Result<Database> {
+        let conn = &mut PgConnection::establish(url)?;
 
-        Ok(macro_::table.select(Macro::as_select()).load(&mut conn)?)
+        conn.run_pending_migrations(POSTGRES_MIGRATIONS)
+            .map_err(|why| anyhow!("Migration failed: {why}"))?;
+
+        let manager = ConnectionManager::<PgConnection>::new(url);
+        let pool = Pool::builder().build(manager)?;
+
+        Ok(Self {
+            pool: DatabasePool::Postgres(pool),
+            cache: Arc::default(),
+        })
     }
 
-    pub fn get_macro(&self, name: impl AsRef<str>) -> Result<Option<(Macro, Vec<Attachment>)>> {
-        let mut conn = self.pool.get()?;
+    #[cfg(not(feature = "postgres"))]
+    fn connect_postgres(_: &str) -> Result<Database> {
+        Err(anyhow!(
+            "PostgreSQL support is not enabled, build with `--features postgres` to use it"
+        ))
+    }
 
-        let r#macro = match macro_::table
-            .filter(macro_::name.eq(name.as_ref()))
-            .select(Macro::as_select())
-            .get_result(&mut conn)
-            .optional()?
-        {
-            Some(r#macro) => r#macro,
-            None => return Ok(None),
-        };
+    /// Whether the database can be backed up with [`Database::backup`]
+    pub fn supports_backups(&self) -> bool {
+        match self.pool {
+            DatabasePool::Sqlite(_) => true,
+            #[cfg(feature = "postgres")]
+            DatabasePool::Postgres(_) => false,
+        }
+    }
+
+    /// Move macros that were created before macros were scoped per guild into `guild_id`
+    pub async fn assign_unscoped_macros(&self, guild_id: &str) -> Result<usize> {
+        let guild_id = guild_id.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                diesel::update(alias::table.filter(alias::guild_id.eq("")))
+                    .set(alias::guild_id.eq(&guild_id))
+                    .execute(conn)?;
+
+                diesel::update(macro_::table.filter(macro_::guild_id.eq("")))
+                    .set(macro_::guild_id.eq(&guild_id))
+                    .execute(conn)
+            })?;
+
+            cache.clear();
+
+            Ok(result)
+        })
+    }
+
+    /// Count the macros that were created before macros were scoped per guild, which can't be
+    /// used until they are moved into a guild with [`Database::assign_unscoped_macros`]
+    pub async fn count_unscoped_macros(&self) -> Result<i64> {
+        with_connection!(self, |conn| {
+            Ok(macro_::table
+                .filter(macro_::guild_id.eq(""))
+                .count()
+                .get_result(conn)?)
+        })
+    }
+
+    /// Write a consistent copy of the database to `path` while it stays in use
+    ///
+    /// Diesel doesn't expose SQLite's backup API, `VACUUM INTO` takes a snapshot the same way from
+    /// a pooled connection and compacts the copy as well.
+    ///
+    /// Only SQLite databases can be backed up, PostgreSQL has its own tools for that.
+    pub async fn backup(&self, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| anyhow!("Backup path is not valid UTF-8"))?
+            .to_owned();
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || match &pool {
+            DatabasePool::Sqlite(pool) => {
+                diesel::sql_query("VACUUM INTO ?")
+                    .bind::<diesel::sql_types::Text, _>(path)
+                    .execute(&mut pool.get()?)?;
+
+                Ok(())
+            }
+            #[cfg(feature = "postgres")]
+            DatabasePool::Postgres(_) => Err(anyhow!("Only SQLite databases can be backed up")),
+        })
+        .await?
+    }
+
+    pub async fn get_macros(&self, guild_id: &str) -> Result<Vec<Macro>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(macro_::table
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .order(macro_::name)
+                .select(Macro::as_select())
+                .load(conn)?)
+        })
+    }
+
+    /// Get the macros of a guild along with their attachments, sorted by name
+    pub async fn get_macros_with_attachments(
+        &self,
+        guild_id: &str,
+    ) -> Result<Vec<(Macro, Vec<Attachment>)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            let macros = macro_::table
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .order(macro_::name)
+                .select(Macro::as_select())
+                .load(conn)?;
+
+            let attachments = Attachment::belonging_to(&macros)
+                .order(attachment::id)
+                .select(Attachment::as_select())
+                .load(conn)?
+                .grouped_by(&macros);
+
+            Ok(macros.into_iter().zip(attachments).collect())
+        })
+    }
+
+    /// Get the tags of all macros of a guild as pairs of macro ids and tag names
+    pub async fn get_macro_tags(&self, guild_id: &str) -> Result<Vec<(i32, String)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(macro_tag::table
+                .inner_join(tag::table)
+                .inner_join(macro_::table)
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .order(tag::name)
+                .select((macro_tag::macro_id, tag::name))
+                .load(conn)?)
+        })
+    }
+
+    /// Get the names of the tags that are in use in a guild
+    pub async fn get_tags(&self, guild_id: &str) -> Result<Vec<String>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(macro_tag::table
+                .inner_join(tag::table)
+                .inner_join(macro_::table)
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .order(tag::name)
+                .select(tag::name)
+                .distinct()
+                .load(conn)?)
+        })
+    }
+
+    /// Look up a macro by its name or one of its aliases
+    pub async fn get_macro(
+        &self,
+        guild_id: &str,
+        name: impl AsRef<str>,
+    ) -> Result<Option<(Macro, Vec<Attachment>)>> {
+        let guild_id = guild_id.to_owned();
+        let name = name.as_ref().to_owned();
+
+        with_connection!(self, |conn| {
+            let r#macro = match macro_::table
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .filter(macro_::name.eq(&name))
+                .select(Macro::as_select())
+                .get_result(conn)
+                .optional()?
+            {
+                Some(r#macro) => Some(r#macro),
+                None => alias::table
+                    .inner_join(macro_::table)
+                    .filter(macro_::guild_id.eq(&guild_id))
+                    .filter(macro_::deleted_at.is_null())
+                    .filter(alias::name.eq(&name))
+                    .select(Macro::as_select())
+                    .get_result(conn)
+                    .optional()?,
+            };
 
-        let attachments = Attachment::belonging_to(&r#macro)
-            .select(Attachment::as_select())
-            .load(&mut conn)?;
+            let Some(r#macro) = r#macro else {
+                return Ok(None);
+            };
 
-        Ok(Some((r#macro, attachments)))
+            let attachments = Attachment::belonging_to(&r#macro)
+                .select(Attachment::as_select())
+                .load(conn)?;
+
+            Ok(Some((r#macro, attachments)))
+        })
     }
 
-    pub fn create_macro(
+    /// Look up a macro by its name or one of its aliases without waiting for the database, unless
+    /// the macros of the guild have changed since they were last looked up
+    pub async fn get_cached_macro(
         &self,
+        guild_id: &str,
         name: &str,
-        description: &str,
-        channel_id: &str,
-        message_id: &str,
-        content: &str,
-        attachments: &[String],
-    ) -> Result<()> {
-        let mut conn = self.pool.get()?;
-
-        conn.transaction::<(), diesel::result::Error, _>(|conn| {
-            let r#macro = diesel::insert_into(macro_::table)
-                .values(&NewMacro {
-                    name,
-                    description,
-                    channel_id,
-                    message_id,
-                    content,
+    ) -> Result<Option<Arc<CachedMacro>>> {
+        let macros = match self.cache.get(guild_id) {
+            Some(macros) => macros,
+            None => self.load_cached_macros(guild_id).await?,
+        };
+
+        Ok(macros.get(name).cloned())
+    }
+
+    /// Load the macros of every guild into the cache, returns how many macros were loaded
+    ///
+    /// Aliases are cached next to the macros they belong to, so only the entries stored under
+    /// the macro's own name are counted.
+    pub async fn load_cache(&self) -> Result<usize> {
+        let mut count = 0;
+
+        for guild_id in self.get_guild_ids().await? {
+            count += self
+                .load_cached_macros(&guild_id)
+                .await?
+                .iter()
+                .filter(|(name, cached)| **name == cached.r#macro.name)
+                .count();
+        }
+
+        Ok(count)
+    }
+
+    async fn load_cached_macros(&self, guild_id: &str) -> Result<Arc<GuildMacros>> {
+        let generation = self.cache.generation();
+        let mut macros = GuildMacros::new();
+        let mut aliases = vec![];
+
+        for (r#macro, attachments, variants, macro_aliases) in
+            self.get_macro_details(guild_id).await?
+        {
+            let cached = Arc::new(CachedMacro::new(r#macro, attachments, variants));
+
+            for alias in macro_aliases {
+                aliases.push((alias.name, cached.clone()));
+            }
+
+            macros.insert(cached.r#macro.name.clone(), cached);
+        }
+
+        // Names take precedence over aliases, the same way as when looking macros up
+        for (name, cached) in aliases {
+            macros.entry(name).or_insert(cached);
+        }
+
+        Ok(self.cache.insert(guild_id.to_owned(), macros, generation))
+    }
+
+    /// Get the guilds that have macros
+    async fn get_guild_ids(&self) -> Result<Vec<String>> {
+        with_connection!(self, |conn| {
+            Ok(macro_::table
+                .filter(macro_::deleted_at.is_null())
+                .select(macro_::guild_id)
+                .distinct()
+                .load(conn)?)
+        })
+    }
+
+    /// Get the macros of a guild along with their attachments, variants and aliases
+    #[allow(clippy::type_complexity)]
+    async fn get_macro_details(
+        &self,
+        guild_id: &str,
+    ) -> Result<Vec<(Macro, Vec<Attachment>, Vec<Variant>, Vec<Alias>)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            let macros = macro_::table
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_null())
+                .select(Macro::as_select())
+                .load(conn)?;
+
+            let attachments = Attachment::belonging_to(&macros)
+                .order(attachment::id)
+                .select(Attachment::as_select())
+                .load(conn)?
+                .grouped_by(&macros);
+            let variants = Variant::belonging_to(&macros)
+                .order(variant::id)
+                .select(Variant::as_select())
+                .load(conn)?
+                .grouped_by(&macros);
+            let aliases = Alias::belonging_to(&macros)
+                .select(Alias::as_select())
+                .load(conn)?
+                .grouped_by(&macros);
+
+            Ok(macros
+                .into_iter()
+                .zip(attachments)
+                .zip(variants)
+                .zip(aliases)
+                .map(|(((r#macro, attachments), variants), aliases)| {
+                    (r#macro, attachments, variants, aliases)
                 })
-                .on_conflict(macro_::name)
-                .do_update()
-                .set((
-                    macro_::description.eq(description),
-                    macro_::channel_id.eq(channel_id),
-                    macro_::message_id.eq(message_id),
-                    macro_::content.eq(content),
-                ))
-                .returning(Macro::as_returning())
-                .get_result(conn)?;
+                .collect())
+        })
+    }
+
+    /// Create a macro, or replace the content and attachments of an existing one
+    ///
+    /// The new version is saved as a revision, so that the previous ones can be restored. The
+    /// creator of an existing macro is kept.
+    pub async fn create_macro(
+        &self,
+        new: NewMacro,
+        attachments: Vec<AttachmentInfo>,
+        tags: Vec<String>,
+    ) -> Result<()> {
+        let cache = self.cache.clone();
 
-            // In the case of an update, delete old attachments
-            diesel::delete(attachment::table)
-                .filter(attachment::macro_id.eq(r#macro.id))
+        with_connection!(self, |conn| {
+            conn.transaction(|conn| conn.upsert_macro(&new, &attachments, &tags))?;
+
+            cache.invalidate(&new.guild_id);
+
+            Ok(())
+        })
+    }
+
+    /// Create or replace the given macros and move the deleted ones to the trash, either all of
+    /// the changes are made or none of them are
+    pub async fn import_macros(
+        &self,
+        guild_id: &str,
+        macros: Vec<(NewMacro, Vec<AttachmentInfo>, Vec<String>)>,
+        deleted: Vec<String>,
+        timestamp: i64,
+    ) -> Result<()> {
+        let guild_id = guild_id.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            conn.transaction::<(), diesel::result::Error, _>(|conn| {
+                for (new, attachments, tags) in &macros {
+                    conn.upsert_macro(new, attachments, tags)?;
+                }
+
+                diesel::update(
+                    macro_::table
+                        .filter(macro_::guild_id.eq(&guild_id))
+                        .filter(macro_::deleted_at.is_null())
+                        .filter(macro_::name.eq_any(&deleted)),
+                )
+                .set(macro_::deleted_at.eq(timestamp))
                 .execute(conn)?;
 
-            let attachments = attachments
-                .iter()
-                .map(|link| NewAttachment {
-                    link,
-                    macro_id: r#macro.id,
+                Ok(())
+            })?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(())
+        })
+    }
+
+    /// Move a macro to the trash, from which it can be restored until it is purged
+    pub async fn delete_macro(&self, guild_id: &str, name: &str, timestamp: i64) -> Result<bool> {
+        let guild_id = guild_id.to_owned();
+        let name = name.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let result = diesel::update(
+                macro_::table
+                    .filter(macro_::guild_id.eq(&guild_id))
+                    .filter(macro_::deleted_at.is_null())
+                    .filter(macro_::name.eq(&name)),
+            )
+            .set(macro_::deleted_at.eq(timestamp))
+            .execute(conn)?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(result > 0)
+        })
+    }
+
+    /// Get the macros of a guild that are in the trash, most recently deleted first
+    pub async fn get_deleted_macros(&self, guild_id: &str) -> Result<Vec<Macro>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(macro_::table
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(macro_::deleted_at.is_not_null())
+                .order(macro_::deleted_at.desc())
+                .select(Macro::as_select())
+                .load(conn)?)
+        })
+    }
+
+    pub async fn restore_macro(&self, guild_id: &str, name: &str) -> Result<bool> {
+        let guild_id = guild_id.to_owned();
+        let name = name.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let result = diesel::update(
+                macro_::table
+                    .filter(macro_::guild_id.eq(&guild_id))
+                    .filter(macro_::deleted_at.is_not_null())
+                    .filter(macro_::name.eq(&name)),
+            )
+            .set(macro_::deleted_at.eq(None::<i64>))
+            .execute(conn)?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(result > 0)
+        })
+    }
+
+    /// Permanently delete macros that were moved to the trash before `before`
+    pub async fn purge_deleted_macros(&self, before: i64) -> Result<usize> {
+        with_connection!(self, |conn| {
+            let result = diesel::delete(macro_::table.filter(macro_::deleted_at.lt(before)))
+                .execute(conn)?;
+
+            Ok(result)
+        })
+    }
+
+    /// Search the names, descriptions and contents of the macros of a guild, best matches first
+    ///
+    /// Every word of the query has to appear in a macro, words match any word that starts with
+    /// them.
+    pub async fn search_macros(
+        &self,
+        guild_id: &str,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchResult>> {
+        use diesel::sql_types::{BigInt, Text};
+
+        let words = query
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+
+        if words.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let guild_id = guild_id.to_owned();
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || match &pool {
+            DatabasePool::Sqlite(pool) => {
+                // Quote every word, so that the query can't contain FTS5 syntax
+                let query = words
+                    .iter()
+                    .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                // Matches in the name weigh more than matches in the description, which weigh
+                // more than matches in the content
+                Ok(diesel::sql_query(
+                    "SELECT macro.name, macro.description, \
+                        snippet(macro_search, 2, '**', '**', '…', 16) AS snippet \
+                    FROM macro_search \
+                    INNER JOIN macro ON macro.id = macro_search.rowid \
+                    WHERE macro_search MATCH ? AND macro.guild_id = ? \
+                        AND macro.deleted_at IS NULL \
+                    ORDER BY bm25(macro_search, 10.0, 5.0, 1.0) \
+                    LIMIT ?",
+                )
+                .bind::<Text, _>(query)
+                .bind::<Text, _>(&guild_id)
+                .bind::<BigInt, _>(limit)
+                .load(&mut pool.get()?)?)
+            }
+            #[cfg(feature = "postgres")]
+            DatabasePool::Postgres(pool) => {
+                // Quote every word, so that the query can't contain tsquery syntax
+                let query = words
+                    .iter()
+                    .map(|word| format!("'{}':*", word.replace('\\', "\\\\").replace('\'', "''")))
+                    .collect::<Vec<_>>()
+                    .join(" & ");
+
+                // The search column weighs the name, description and content of a macro
+                Ok(diesel::sql_query(
+                    "SELECT name, description, \
+                        ts_headline('simple', content, to_tsquery('simple', $1), \
+                            'StartSel=**, StopSel=**, MaxWords=16, MinWords=8') AS snippet \
+                    FROM macro \
+                    WHERE search @@ to_tsquery('simple', $1) AND guild_id = $2 \
+                        AND deleted_at IS NULL \
+                    ORDER BY ts_rank(search, to_tsquery('simple', $1)) DESC \
+                    LIMIT $3",
+                )
+                .bind::<Text, _>(query)
+                .bind::<Text, _>(&guild_id)
+                .bind::<BigInt, _>(limit)
+                .load(&mut pool.get()?)?)
+            }
+        })
+        .await?
+    }
+
+    /// Get all aliases of a guild along with the name of the macro they refer to
+    pub async fn get_aliases(&self, guild_id: &str) -> Result<Vec<(Alias, String)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(alias::table
+                .inner_join(macro_::table)
+                .filter(macro_::guild_id.eq(&guild_id))
+                .order((macro_::name, alias::name))
+                .select((Alias::as_select(), macro_::name))
+                .load(conn)?)
+        })
+    }
+
+    /// Get the aliases of a macro, including those of macros in the trash
+    pub async fn get_macro_aliases(&self, macro_id: i32) -> Result<Vec<Alias>> {
+        with_connection!(self, |conn| {
+            Ok(alias::table
+                .filter(alias::macro_id.eq(macro_id))
+                .order(alias::name)
+                .select(Alias::as_select())
+                .load(conn)?)
+        })
+    }
+
+    /// Add an alias to a macro, returns `None` if another alias in the guild already has the name,
+    /// which includes the aliases of macros in the trash
+    pub async fn create_alias(&self, macro_id: i32, name: &str) -> Result<Option<Alias>> {
+        let name = name.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let guild_id = macro_::table
+                .find(macro_id)
+                .select(macro_::guild_id)
+                .get_result::<String>(conn)?;
+
+            let alias = diesel::insert_into(alias::table)
+                .values(&NewAlias {
+                    macro_id,
+                    guild_id: &guild_id,
+                    name: &name,
                 })
-                .collect::<Vec<_>>();
+                .on_conflict_do_nothing()
+                .returning(Alias::as_returning())
+                .get_result(conn)
+                .optional()?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(alias)
+        })
+    }
+
+    /// Remove an alias, including those of macros in the trash
+    pub async fn delete_alias(&self, guild_id: &str, name: &str) -> Result<bool> {
+        let guild_id = guild_id.to_owned();
+        let name = name.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let result = diesel::delete(
+                alias::table
+                    .filter(alias::guild_id.eq(&guild_id))
+                    .filter(alias::name.eq(&name)),
+            )
+            .execute(conn)?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(result > 0)
+        })
+    }
+
+    /// Get all versions of a macro, newest first
+    pub async fn get_revisions(&self, macro_id: i32) -> Result<Vec<Revision>> {
+        with_connection!(self, |conn| {
+            Ok(macro_revision::table
+                .filter(macro_revision::macro_id.eq(macro_id))
+                .order(macro_revision::revision.desc())
+                .select(Revision::as_select())
+                .load(conn)?)
+        })
+    }
+
+    /// Restore a previous version of a macro, which is saved as a new revision
+    ///
+    /// Returns `None` if the macro has no such revision.
+    pub async fn rollback_macro(
+        &self,
+        macro_id: i32,
+        revision: i32,
+        editor: &str,
+        timestamp: i64,
+    ) -> Result<Option<Revision>> {
+        let editor = editor.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let revision = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                let Some(old) = macro_revision::table
+                    .filter(macro_revision::macro_id.eq(macro_id))
+                    .filter(macro_revision::revision.eq(revision))
+                    .select(Revision::as_select())
+                    .get_result(conn)
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
 
-            diesel::insert_into(attachment::table)
-                .values(&attachments)
+                let attachments = RevisionAttachment::belonging_to(&old)
+                    .order(revision_attachment::id)
+                    .select(RevisionAttachment::as_select())
+                    .load(conn)?;
+                let attachments = attachments
+                    .iter()
+                    .map(RevisionAttachment::info)
+                    .collect::<Vec<_>>();
+
+                let r#macro = diesel::update(macro_::table.find(macro_id))
+                    .set((
+                        macro_::description.eq(&old.description),
+                        macro_::channel_id.eq(&old.channel_id),
+                        macro_::message_id.eq(&old.message_id),
+                        macro_::content.eq(&old.content),
+                        macro_::updated_by.eq(&editor),
+                        macro_::updated_at.eq(timestamp),
+                    ))
+                    .returning(Macro::as_returning())
+                    .get_result(conn)?;
+
+                conn.replace_attachments(macro_id, &attachments)?;
+                conn.remove_stale_variants(macro_id, attachments.len())?;
+                conn.save_revision(&r#macro, &attachments).map(Some)
+            })?;
+
+            cache.clear();
+
+            Ok(revision)
+        })
+    }
+
+    pub async fn get_variants(&self, macro_id: i32) -> Result<Vec<Variant>> {
+        with_connection!(self, |conn| {
+            Ok(variant::table
+                .filter(variant::macro_id.eq(macro_id))
+                .order(variant::id)
+                .select(Variant::as_select())
+                .load(conn)?)
+        })
+    }
+
+    /// Add an alternative body to a macro, returns `None` if the macro doesn't exist
+    pub async fn create_variant(
+        &self,
+        macro_id: i32,
+        content: &str,
+        weight: i32,
+    ) -> Result<Option<Variant>> {
+        let content = content.to_owned();
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let Some(guild_id) = macro_::table
+                .find(macro_id)
+                .filter(macro_::deleted_at.is_null())
+                .select(macro_::guild_id)
+                .get_result::<String>(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            let variant = diesel::insert_into(variant::table)
+                .values(&NewVariant {
+                    macro_id,
+                    content: &content,
+                    weight,
+                })
+                .returning(Variant::as_returning())
+                .get_result(conn)?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(Some(variant))
+        })
+    }
+
+    pub async fn delete_variant(&self, macro_id: i32, id: i32) -> Result<bool> {
+        let cache = self.cache.clone();
+
+        with_connection!(self, |conn| {
+            let Some(guild_id) = macro_::table
+                .find(macro_id)
+                .select(macro_::guild_id)
+                .get_result::<String>(conn)
+                .optional()?
+            else {
+                return Ok(false);
+            };
+
+            let result = diesel::delete(
+                variant::table
+                    .filter(variant::id.eq(id))
+                    .filter(variant::macro_id.eq(macro_id)),
+            )
+            .execute(conn)?;
+
+            cache.invalidate(&guild_id);
+
+            Ok(result > 0)
+        })
+    }
+
+    pub async fn record_invocation(
+        &self,
+        macro_id: i32,
+        user_id: &str,
+        channel_id: &str,
+        timestamp: i64,
+        source: Source,
+    ) -> Result<()> {
+        let user_id = user_id.to_owned();
+        let channel_id = channel_id.to_owned();
+
+        with_connection!(self, |conn| {
+            diesel::insert_into(invocation::table)
+                .values(&NewInvocation {
+                    macro_id,
+                    user_id: &user_id,
+                    channel_id: &channel_id,
+                    timestamp,
+                    source: source.as_str(),
+                })
                 .execute(conn)?;
 
             Ok(())
-        })?;
+        })
+    }
 
-        Ok(())
+    /// Get the most used macros of a guild since `since`, along with how often they were used
+    pub async fn get_top_macros(
+        &self,
+        guild_id: &str,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            Ok(invocation::table
+                .inner_join(macro_::table)
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(invocation::timestamp.ge(since))
+                .group_by((macro_::id, macro_::name))
+                .select((macro_::name, count_star()))
+                .order((count_star().desc(), macro_::name))
+                .limit(limit)
+                .load(conn)?)
+        })
     }
 
-    pub fn delete_macro(&self, name: &str) -> Result<bool> {
-        let mut conn = self.pool.get()?;
+    /// Get the users that invoked macros of a guild the most since `since`, optionally only
+    /// counting a single macro
+    pub async fn get_top_invokers(
+        &self,
+        guild_id: &str,
+        macro_id: Option<i32>,
+        since: i64,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let guild_id = guild_id.to_owned();
+
+        with_connection!(self, |conn| {
+            let mut query = invocation::table
+                .inner_join(macro_::table)
+                .filter(macro_::guild_id.eq(&guild_id))
+                .filter(invocation::timestamp.ge(since))
+                .group_by(invocation::user_id)
+                .select((invocation::user_id, count_star()))
+                .order((count_star().desc(), invocation::user_id))
+                .limit(limit)
+                .into_boxed();
 
-        let result =
-            diesel::delete(macro_::dsl::macro_.filter(macro_::name.eq(name))).execute(&mut conn)?;
+            if let Some(macro_id) = macro_id {
+                query = query.filter(invocation::macro_id.eq(macro_id));
+            }
 
-        Ok(result > 0)
+            Ok(query.load(conn)?)
+        })
+    }
+
+    /// Count how often a macro was used since `since`
+    pub async fn count_invocations(&self, macro_id: i32, since: i64) -> Result<i64> {
+        with_connection!(self, |conn| {
+            Ok(invocation::table
+                .filter(invocation::macro_id.eq(macro_id))
+                .filter(invocation::timestamp.ge(since))
+                .count()
+                .get_result(conn)?)
+        })
     }
 }
+
+/// Queries that run as part of larger transactions, implemented for the connection of every
+/// backend
+trait MacroConnection {
+    /// Create a macro, or replace the content, attachments and tags of an existing one
+    fn upsert_macro(
+        &mut self,
+        new: &NewMacro,
+        attachments: &[AttachmentInfo],
+        tags: &[String],
+    ) -> QueryResult<Macro>;
+
+    fn replace_attachments(
+        &mut self,
+        macro_id: i32,
+        attachments: &[AttachmentInfo],
+    ) -> QueryResult<()>;
+
+    /// Save the current version of a macro as its newest revision
+    fn save_revision(
+        &mut self,
+        r#macro: &Macro,
+        attachments: &[AttachmentInfo],
+    ) -> QueryResult<Revision>;
+
+    /// Replace the tags of a macro, tags that are no longer used by any macro are removed
+    fn replace_tags(&mut self, r#macro: &Macro, tags: &[String]) -> QueryResult<()>;
+
+    /// Remove the variants of a macro that need more attachments than it has, variants are only
+    /// checked against the attachments of the macro when they are added
+    fn remove_stale_variants(&mut self, macro_id: i32, attachments: usize) -> QueryResult<()>;
+}
+
+macro_rules! impl_macro_connection {
+    ($connection:ty) => {
+        impl MacroConnection for $connection {
+            fn upsert_macro(
+                &mut self,
+                new: &NewMacro,
+                attachments: &[AttachmentInfo],
+                tags: &[String],
+            ) -> QueryResult<Macro> {
+                let r#macro = diesel::insert_into(macro_::table)
+                    .values(new)
+                    .on_conflict((macro_::guild_id, macro_::name))
+                    .do_update()
+                    .set((
+                        macro_::description.eq(&new.description),
+                        macro_::channel_id.eq(&new.channel_id),
+                        macro_::message_id.eq(&new.message_id),
+                        macro_::content.eq(&new.content),
+                        macro_::updated_by.eq(&new.updated_by),
+                        macro_::updated_at.eq(new.updated_at),
+                        // Recreating a macro that is in the trash restores it
+                        macro_::deleted_at.eq(None::<i64>),
+                    ))
+                    .returning(Macro::as_returning())
+                    .get_result(self)?;
+
+                self.replace_attachments(r#macro.id, attachments)?;
+                self.remove_stale_variants(r#macro.id, attachments.len())?;
+                self.save_revision(&r#macro, attachments)?;
+                self.replace_tags(&r#macro, tags)?;
+
+                Ok(r#macro)
+            }
+
+            fn replace_attachments(
+                &mut self,
+                macro_id: i32,
+                attachments: &[AttachmentInfo],
+            ) -> QueryResult<()> {
+                diesel::delete(attachment::table)
+                    .filter(attachment::macro_id.eq(macro_id))
+                    .execute(self)?;
+
+                let attachments = attachments
+                    .iter()
+                    .map(|attachment| NewAttachment {
+                        macro_id,
+                        link: &attachment.link,
+                        filename: &attachment.filename,
+                        size: attachment.size,
+                        content_type: attachment.content_type.as_deref(),
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(attachment::table)
+                    .values(&attachments)
+                    .execute(self)?;
+
+                Ok(())
+            }
+
+            fn save_revision(
+                &mut self,
+                r#macro: &Macro,
+                attachments: &[AttachmentInfo],
+            ) -> QueryResult<Revision> {
+                let latest = macro_revision::table
+                    .filter(macro_revision::macro_id.eq(r#macro.id))
+                    .select(diesel::dsl::max(macro_revision::revision))
+                    .get_result::<Option<i32>>(self)?;
+
+                let revision = diesel::insert_into(macro_revision::table)
+                    .values(&NewRevision {
+                        macro_id: r#macro.id,
+                        revision: latest.unwrap_or_default() + 1,
+                        description: &r#macro.description,
+                        channel_id: &r#macro.channel_id,
+                        message_id: &r#macro.message_id,
+                        content: &r#macro.content,
+                        edited_by: r#macro.updated_by.as_deref(),
+                        edited_at: r#macro.updated_at,
+                    })
+                    .returning(Revision::as_returning())
+                    .get_result(self)?;
+
+                let attachments = attachments
+                    .iter()
+                    .map(|attachment| NewRevisionAttachment {
+                        revision_id: revision.id,
+                        link: &attachment.link,
+                        filename: &attachment.filename,
+                        size: attachment.size,
+                        content_type: attachment.content_type.as_deref(),
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(revision_attachment::table)
+                    .values(&attachments)
+                    .execute(self)?;
+
+                Ok(revision)
+            }
+
+            fn replace_tags(&mut self, r#macro: &Macro, tags: &[String]) -> QueryResult<()> {
+                diesel::delete(macro_tag::table)
+                    .filter(macro_tag::macro_id.eq(r#macro.id))
+                    .execute(self)?;
+
+                for name in tags {
+                    let tag = diesel::insert_into(tag::table)
+                        .values(&NewTag {
+                            guild_id: &r#macro.guild_id,
+                            name,
+                        })
+                        .on_conflict((tag::guild_id, tag::name))
+                        .do_update()
+                        .set(tag::name.eq(name))
+                        .returning(Tag::as_returning())
+                        .get_result(self)?;
+
+                    diesel::insert_into(macro_tag::table)
+                        .values(&NewMacroTag {
+                            macro_id: r#macro.id,
+                            tag_id: tag.id,
+                        })
+                        .on_conflict_do_nothing()
+                        .execute(self)?;
+                }
+
+                let used = macro_tag::table.select(macro_tag::tag_id);
+
+                diesel::delete(tag::table)
+                    .filter(tag::guild_id.eq(&r#macro.guild_id))
+                    .filter(diesel::dsl::not(tag::id.eq_any(used)))
+                    .execute(self)?;
+
+                Ok(())
+            }
+
+            fn remove_stale_variants(
+                &mut self,
+                macro_id: i32,
+                attachments: usize,
+            ) -> QueryResult<()> {
+                let stale = variant::table
+                    .filter(variant::macro_id.eq(macro_id))
+                    .select(Variant::as_select())
+                    .load(self)?
+                    .into_iter()
+                    .filter(|variant| {
+                        ParameterizedString::new(&variant.content)
+                            .and_then(|pstring| pstring.check_attachments(attachments))
+                            .is_err()
+                    })
+                    .map(|variant| variant.id)
+                    .collect::<Vec<_>>();
+
+                diesel::delete(variant::table.filter(variant::id.eq_any(stale))).execute(self)?;
+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_macro_connection!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_macro_connection!(PgConnection);
+
+// Declared last, so that the tests can use `with_connection!`
+#[cfg(test)]
+mod tests;
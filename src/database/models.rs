@@ -1,17 +1,29 @@
 use diesel::prelude::*;
+use poise::serenity_prelude as serenity;
 
-use super::schema::{attachment, macro_};
+use super::schema::{
+    alias, attachment, invocation, macro_, macro_revision, macro_tag, revision_attachment, tag,
+    variant,
+};
 
 #[derive(Queryable, Selectable, Identifiable, Debug, PartialEq)]
 #[diesel(table_name = macro_)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Macro {
     pub id: i32,
+    pub guild_id: String,
     pub name: String,
     pub description: String,
     pub channel_id: String,
     pub message_id: String,
     pub content: String,
+    /// When the macro was moved to the trash, deleted macros are purged after a while
+    pub deleted_at: Option<i64>,
+    /// Who created the macro and when, unknown for macros that predate the revision history
+    pub created_by: Option<String>,
+    pub created_at: Option<i64>,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<i64>,
 }
 
 #[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
@@ -22,23 +34,245 @@ pub struct Attachment {
     pub id: i32,
     pub macro_id: i32,
     pub link: String,
+    pub filename: String,
+    pub size: i32,
+    pub content_type: Option<String>,
+}
+
+impl Attachment {
+    pub fn info(&self) -> AttachmentInfo {
+        AttachmentInfo {
+            link: self.link.clone(),
+            filename: self.filename.clone(),
+            size: self.size,
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Macro))]
+#[diesel(table_name = variant)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Variant {
+    pub id: i32,
+    pub macro_id: i32,
+    pub content: String,
+    pub weight: i32,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Macro))]
+#[diesel(table_name = alias)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Alias {
+    pub id: i32,
+    pub macro_id: i32,
+    pub guild_id: String,
+    pub name: String,
+}
+
+/// A version of a macro, the latest revision matches the macro itself
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Macro))]
+#[diesel(table_name = macro_revision)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Revision {
+    pub id: i32,
+    pub macro_id: i32,
+    pub revision: i32,
+    pub description: String,
+    pub channel_id: String,
+    pub message_id: String,
+    pub content: String,
+    pub edited_by: Option<String>,
+    pub edited_at: Option<i64>,
+}
+
+#[derive(Queryable, Selectable, Identifiable, Associations, Debug, PartialEq)]
+#[diesel(belongs_to(Revision))]
+#[diesel(table_name = revision_attachment)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RevisionAttachment {
+    pub id: i32,
+    pub revision_id: i32,
+    pub link: String,
+    pub filename: String,
+    pub size: i32,
+    pub content_type: Option<String>,
+}
+
+impl RevisionAttachment {
+    pub fn info(&self) -> AttachmentInfo {
+        AttachmentInfo {
+            link: self.link.clone(),
+            filename: self.filename.clone(),
+            size: self.size,
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Debug, PartialEq)]
+#[diesel(table_name = tag)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Tag {
+    pub id: i32,
+    pub guild_id: String,
+    pub name: String,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = macro_)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct NewMacro<'a> {
-    pub name: &'a str,
+pub struct NewMacro {
+    pub guild_id: String,
+    pub name: String,
+    pub description: String,
+    pub channel_id: String,
+    pub message_id: String,
+    pub content: String,
+    pub created_by: Option<String>,
+    pub created_at: Option<i64>,
+    pub updated_by: Option<String>,
+    pub updated_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = attachment)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewAttachment<'a> {
+    pub macro_id: i32,
+    pub link: &'a str,
+    pub filename: &'a str,
+    pub size: i32,
+    pub content_type: Option<&'a str>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = macro_revision)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewRevision<'a> {
+    pub macro_id: i32,
+    pub revision: i32,
     pub description: &'a str,
     pub channel_id: &'a str,
     pub message_id: &'a str,
     pub content: &'a str,
+    pub edited_by: Option<&'a str>,
+    pub edited_at: Option<i64>,
 }
 
 #[derive(Insertable)]
-#[diesel(table_name = attachment)]
+#[diesel(table_name = revision_attachment)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct NewAttachment<'a> {
-    pub macro_id: i32,
+pub struct NewRevisionAttachment<'a> {
+    pub revision_id: i32,
     pub link: &'a str,
+    pub filename: &'a str,
+    pub size: i32,
+    pub content_type: Option<&'a str>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tag)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewTag<'a> {
+    pub guild_id: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = macro_tag)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewMacroTag {
+    pub macro_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = variant)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewVariant<'a> {
+    pub macro_id: i32,
+    pub content: &'a str,
+    pub weight: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = alias)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewAlias<'a> {
+    pub macro_id: i32,
+    pub guild_id: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = invocation)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NewInvocation<'a> {
+    pub macro_id: i32,
+    pub user_id: &'a str,
+    pub channel_id: &'a str,
+    pub timestamp: i64,
+    pub source: &'a str,
+}
+
+/// Where the content of an invoked macro came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// The up-to-date source message
+    Live,
+    /// The copy stored in the database, used for variants and when the source message is gone
+    Database,
+}
+
+impl Source {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Live => "live",
+            Source::Database => "database",
+        }
+    }
+}
+
+/// A macro that matches a full-text search
+#[derive(QueryableByName, Debug, PartialEq)]
+pub struct SearchResult {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub description: String,
+    /// The part of the macro that matched, with the matching terms in bold
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub snippet: String,
+}
+
+/// Details of a file attached to a macro, independent of where it is stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentInfo {
+    pub link: String,
+    pub filename: String,
+    /// In bytes, unknown (0) for attachments that were stored before sizes were recorded
+    pub size: i32,
+    pub content_type: Option<String>,
+}
+
+impl From<&serenity::Attachment> for AttachmentInfo {
+    fn from(attachment: &serenity::Attachment) -> Self {
+        Self {
+            link: attachment.url.clone(),
+            filename: attachment.filename.clone(),
+            size: attachment.size as i32,
+            content_type: attachment.content_type.clone(),
+        }
+    }
+}
+
+impl AttachmentInfo {
+    /// Files are marked as a spoiler in Discord by prefixing their name
+    pub fn spoiler(&self) -> bool {
+        self.filename.starts_with("SPOILER_")
+    }
 }
@@ -1,10 +1,33 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    alias (id) {
+        id -> Integer,
+        macro_id -> Integer,
+        guild_id -> Text,
+        name -> Text,
+    }
+}
+
 diesel::table! {
     attachment (id) {
         id -> Integer,
         macro_id -> Integer,
         link -> Text,
+        filename -> Text,
+        size -> Integer,
+        content_type -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    invocation (id) {
+        id -> Integer,
+        macro_id -> Integer,
+        user_id -> Text,
+        channel_id -> Text,
+        timestamp -> BigInt,
+        source -> Text,
     }
 }
 
@@ -12,17 +35,86 @@ diesel::table! {
     #[sql_name = "macro"]
     macro_ (id) {
         id -> Integer,
+        guild_id -> Text,
         name -> Text,
         description -> Text,
         channel_id -> Text,
         message_id -> Text,
         content -> Text,
+        deleted_at -> Nullable<BigInt>,
+        created_by -> Nullable<Text>,
+        created_at -> Nullable<BigInt>,
+        updated_by -> Nullable<Text>,
+        updated_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    macro_revision (id) {
+        id -> Integer,
+        macro_id -> Integer,
+        revision -> Integer,
+        description -> Text,
+        channel_id -> Text,
+        message_id -> Text,
+        content -> Text,
+        edited_by -> Nullable<Text>,
+        edited_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    macro_tag (macro_id, tag_id) {
+        macro_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    revision_attachment (id) {
+        id -> Integer,
+        revision_id -> Integer,
+        link -> Text,
+        filename -> Text,
+        size -> Integer,
+        content_type -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    tag (id) {
+        id -> Integer,
+        guild_id -> Text,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    variant (id) {
+        id -> Integer,
+        macro_id -> Integer,
+        content -> Text,
+        weight -> Integer,
     }
 }
 
+diesel::joinable!(alias -> macro_ (macro_id));
 diesel::joinable!(attachment -> macro_ (macro_id));
+diesel::joinable!(invocation -> macro_ (macro_id));
+diesel::joinable!(macro_revision -> macro_ (macro_id));
+diesel::joinable!(macro_tag -> macro_ (macro_id));
+diesel::joinable!(macro_tag -> tag (tag_id));
+diesel::joinable!(revision_attachment -> macro_revision (revision_id));
+diesel::joinable!(variant -> macro_ (macro_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    alias,
     attachment,
+    invocation,
     macro_,
+    macro_revision,
+    macro_tag,
+    revision_attachment,
+    tag,
+    variant,
 );
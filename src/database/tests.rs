@@ -0,0 +1,847 @@
+//! Tests that run against every database backend
+//!
+//! SQLite databases are created in the temporary directory. PostgreSQL tests run against the
+//! database in `TEST_POSTGRES_URL` when the `postgres` feature is enabled, and are skipped when it
+//! isn't set. Every test uses its own guild, so the database doesn't need to be empty.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use diesel::{sql_types::BigInt, QueryableByName, RunQueryDsl};
+
+use super::{
+    models::{AttachmentInfo, NewMacro, Source},
+    Database, DatabasePool,
+};
+
+/// Run every test against SQLite and, when enabled, PostgreSQL
+macro_rules! database_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    let database = super::SqliteDatabase::new();
+
+                    super::$name(&database.database, &super::guild_id()).await;
+                }
+            )*
+        }
+
+        #[cfg(feature = "postgres")]
+        mod postgres {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    let Some(database) = super::postgres() else {
+                        eprintln!("TEST_POSTGRES_URL is not set, skipping");
+                        return;
+                    };
+
+                    super::$name(database, &super::guild_id()).await;
+                }
+            )*
+        }
+    };
+}
+
+database_tests! {
+    create_and_get_macro,
+    recreate_macro,
+    long_descriptions_and_links,
+    aliases,
+    trash_and_restore,
+    purge_deleted_macros,
+    variants,
+    rollback_macro,
+    search_macros,
+    import_macros,
+    invocations,
+    cached_macros,
+    slow_queries_do_not_stall_the_runtime,
+}
+
+/// A SQLite database in the temporary directory, which is removed once the test is done
+struct SqliteDatabase {
+    database: Database,
+    path: PathBuf,
+}
+
+impl SqliteDatabase {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "lcvr-macros-test-{}-{}.sqlite3",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        Self {
+            database: Database::connect(path.to_str().unwrap()).unwrap(),
+            path,
+        }
+    }
+}
+
+impl Drop for SqliteDatabase {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// The database shared by the PostgreSQL tests, so that migrations only run once
+#[cfg(feature = "postgres")]
+fn postgres() -> Option<&'static Database> {
+    use std::sync::OnceLock;
+
+    static DATABASE: OnceLock<Option<Database>> = OnceLock::new();
+
+    DATABASE
+        .get_or_init(|| {
+            let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+
+            Some(Database::connect(url).unwrap())
+        })
+        .as_ref()
+}
+
+/// A guild that no other test uses, including those of earlier runs
+fn guild_id() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+    format!(
+        "{}{}",
+        now.as_micros() % 10u128.pow(16),
+        COUNT.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+fn new_macro(guild_id: &str, name: &str, content: &str) -> NewMacro {
+    NewMacro {
+        guild_id: guild_id.to_string(),
+        name: name.to_string(),
+        description: format!("The {name} macro"),
+        channel_id: "1".to_string(),
+        message_id: "2".to_string(),
+        content: content.to_string(),
+        created_by: Some("3".to_string()),
+        created_at: Some(100),
+        updated_by: Some("3".to_string()),
+        updated_at: Some(100),
+    }
+}
+
+fn attachment(filename: &str) -> AttachmentInfo {
+    AttachmentInfo {
+        link: format!("https://cdn.discordapp.com/attachments/1/2/{filename}"),
+        filename: filename.to_string(),
+        size: 1024,
+        content_type: Some("image/png".to_string()),
+    }
+}
+
+fn names<'a>(names: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+    names.into_iter().map(String::as_str).collect()
+}
+
+async fn create_and_get_macro(database: &Database, guild_id: &str) {
+    database
+        .create_macro(
+            new_macro(guild_id, "hello", "Hello {0} {1}"),
+            vec![attachment("a.png"), attachment("b.png")],
+            vec!["greetings".to_string(), "setup".to_string()],
+        )
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "bye", "Bye"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (r#macro, attachments) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r#macro.content, "Hello {0} {1}");
+    assert_eq!(r#macro.created_by.as_deref(), Some("3"));
+    assert_eq!(
+        attachments
+            .iter()
+            .map(|attachment| attachment.info())
+            .collect::<Vec<_>>(),
+        [attachment("a.png"), attachment("b.png")]
+    );
+
+    let macros = database.get_macros(guild_id).await.unwrap();
+    assert_eq!(
+        names(macros.iter().map(|r#macro| &r#macro.name)),
+        ["bye", "hello"]
+    );
+
+    let tags = database.get_macro_tags(guild_id).await.unwrap();
+    assert_eq!(
+        tags,
+        [
+            (r#macro.id, "greetings".to_string()),
+            (r#macro.id, "setup".to_string())
+        ]
+    );
+
+    // Macros are scoped per guild
+    assert!(database
+        .get_macro(&format!("{guild_id}-other"), "hello")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn recreate_macro(database: &Database, guild_id: &str) {
+    database
+        .create_macro(
+            new_macro(guild_id, "hello", "Hello {0}"),
+            vec![attachment("a.png")],
+            vec!["old".to_string()],
+        )
+        .await
+        .unwrap();
+    database
+        .create_macro(
+            new_macro(guild_id, "hello", "Hi"),
+            vec![],
+            vec!["new".to_string()],
+        )
+        .await
+        .unwrap();
+
+    let (r#macro, attachments) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r#macro.content, "Hi");
+    assert!(attachments.is_empty());
+
+    // Tags that are no longer used are removed
+    assert_eq!(database.get_tags(guild_id).await.unwrap(), ["new"]);
+
+    let revisions = database.get_revisions(r#macro.id).await.unwrap();
+    assert_eq!(revisions.len(), 2);
+}
+
+async fn long_descriptions_and_links(database: &Database, guild_id: &str) {
+    let mut new = new_macro(guild_id, "long", "{0}");
+    new.description = "a".repeat(4000);
+
+    let mut file = attachment(&format!("{}.png", "b".repeat(300)));
+    file.link += "?ex=67890abc&is=12345def&hm=0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    database
+        .create_macro(new, vec![file.clone()], vec![])
+        .await
+        .unwrap();
+
+    let (r#macro, attachments) = database.get_macro(guild_id, "long").await.unwrap().unwrap();
+    assert_eq!(r#macro.description.len(), 4000);
+    assert_eq!(attachments[0].info(), file);
+}
+
+async fn aliases(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "hi", "Hi"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (hello, _) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    let (hi, _) = database.get_macro(guild_id, "hi").await.unwrap().unwrap();
+
+    let alias = database
+        .create_alias(hello.id, "hey")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(alias.guild_id, guild_id);
+
+    let (r#macro, _) = database.get_macro(guild_id, "hey").await.unwrap().unwrap();
+    assert_eq!(r#macro.name, "hello");
+
+    let aliases = database.get_aliases(guild_id).await.unwrap();
+    assert_eq!(aliases.len(), 1);
+    assert_eq!(aliases[0].0.name, "hey");
+    assert_eq!(aliases[0].1, "hello");
+
+    // Alias names are unique per guild, even when their macro is in the trash
+    assert!(database.create_alias(hi.id, "hey").await.unwrap().is_none());
+
+    database.delete_macro(guild_id, "hello", 200).await.unwrap();
+    assert!(database.create_alias(hi.id, "hey").await.unwrap().is_none());
+    assert_eq!(database.get_macro_aliases(hello.id).await.unwrap(), [alias]);
+
+    // Aliases of macros in the trash can be removed
+    assert!(database.delete_alias(guild_id, "hey").await.unwrap());
+    assert!(!database.delete_alias(guild_id, "hey").await.unwrap());
+    assert!(database
+        .get_macro_aliases(hello.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let alias = database.create_alias(hi.id, "hey").await.unwrap().unwrap();
+    let (r#macro, _) = database.get_macro(guild_id, "hey").await.unwrap().unwrap();
+    assert_eq!(r#macro.name, "hi");
+
+    // Other guilds can use the same name
+    database
+        .create_macro(
+            new_macro(&format!("{guild_id}-other"), "hi", "Hi"),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let (other, _) = database
+        .get_macro(&format!("{guild_id}-other"), "hi")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(
+        database
+            .create_alias(other.id, "hey")
+            .await
+            .unwrap()
+            .unwrap(),
+        alias
+    );
+}
+
+async fn trash_and_restore(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+
+    assert!(database.delete_macro(guild_id, "hello", 200).await.unwrap());
+    assert!(!database.delete_macro(guild_id, "hello", 200).await.unwrap());
+    assert!(database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .is_none());
+
+    let deleted = database.get_deleted_macros(guild_id).await.unwrap();
+    assert_eq!(
+        names(deleted.iter().map(|r#macro| &r#macro.name)),
+        ["hello"]
+    );
+    assert_eq!(deleted[0].deleted_at, Some(200));
+
+    assert!(database.restore_macro(guild_id, "hello").await.unwrap());
+    assert!(database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .is_some());
+    assert!(database
+        .get_deleted_macros(guild_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Recreating a macro that is in the trash restores it
+    database.delete_macro(guild_id, "hello", 200).await.unwrap();
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hi"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (r#macro, _) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r#macro.content, "Hi");
+    assert_eq!(r#macro.deleted_at, None);
+}
+
+async fn purge_deleted_macros(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "old", "Old"), vec![], vec![])
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "new", "New"), vec![], vec![])
+        .await
+        .unwrap();
+
+    // Other tests trash their macros later than this, so they aren't purged
+    database.delete_macro(guild_id, "old", 1).await.unwrap();
+    database.delete_macro(guild_id, "new", 3).await.unwrap();
+
+    assert!(database.purge_deleted_macros(2).await.unwrap() >= 1);
+
+    let deleted = database.get_deleted_macros(guild_id).await.unwrap();
+    assert_eq!(names(deleted.iter().map(|r#macro| &r#macro.name)), ["new"]);
+}
+
+async fn variants(database: &Database, guild_id: &str) {
+    database
+        .create_macro(
+            new_macro(guild_id, "hello", "Hello {0}"),
+            vec![attachment("a.png")],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let (r#macro, _) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+
+    let needs_file = database
+        .create_variant(r#macro.id, "Hi {0}", 2)
+        .await
+        .unwrap()
+        .unwrap();
+    let plain = database
+        .create_variant(r#macro.id, "Hi", 1)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(needs_file.weight, 2);
+
+    database
+        .create_macro(new_macro(guild_id, "deleted", "Deleted"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (deleted, _) = database
+        .get_macro(guild_id, "deleted")
+        .await
+        .unwrap()
+        .unwrap();
+    database
+        .delete_macro(guild_id, "deleted", 200)
+        .await
+        .unwrap();
+
+    assert!(database
+        .create_variant(deleted.id, "Hi", 1)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Variants that need more attachments than the macro has are removed
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let variants = database.get_variants(plain.macro_id).await.unwrap();
+    assert_eq!(variants, [plain]);
+
+    assert!(database
+        .delete_variant(r#macro.id, variants[0].id)
+        .await
+        .unwrap());
+    assert!(database
+        .get_variants(variants[0].macro_id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+async fn rollback_macro(database: &Database, guild_id: &str) {
+    database
+        .create_macro(
+            new_macro(guild_id, "hello", "Hello {0}"),
+            vec![attachment("a.png")],
+            vec![],
+        )
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hi"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (r#macro, _) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert!(database
+        .rollback_macro(r#macro.id, 10, "4", 300)
+        .await
+        .unwrap()
+        .is_none());
+
+    let revision = database
+        .rollback_macro(r#macro.id, 1, "4", 300)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(revision.revision, 3);
+    assert_eq!(revision.edited_by.as_deref(), Some("4"));
+
+    let (r#macro, attachments) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(r#macro.content, "Hello {0}");
+    assert_eq!(r#macro.updated_at, Some(300));
+    assert_eq!(attachments[0].info(), attachment("a.png"));
+}
+
+async fn search_macros(database: &Database, guild_id: &str) {
+    database
+        .create_macro(
+            new_macro(guild_id, "install", "Download the installer and run it"),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+    database
+        .create_macro(
+            new_macro(guild_id, "crash", "Send your crash log"),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+    database
+        .create_macro(
+            new_macro(guild_id, "logs", "Where the installer keeps its logs"),
+            vec![],
+            vec![],
+        )
+        .await
+        .unwrap();
+
+    let results = database
+        .search_macros(guild_id, "install", 10)
+        .await
+        .unwrap();
+    let mut found = results
+        .iter()
+        .map(|result| result.name.as_str())
+        .collect::<Vec<_>>();
+
+    // Matches in the name rank first
+    assert_eq!(found[0], "install");
+
+    found.sort();
+    assert_eq!(found, ["install", "logs"]);
+
+    // Every word has to match
+    let results = database
+        .search_macros(guild_id, "installer log", 10)
+        .await
+        .unwrap();
+    assert_eq!(names(results.iter().map(|result| &result.name)), ["logs"]);
+
+    assert!(database
+        .search_macros(guild_id, "  ", 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Trashed macros are left out
+    database.delete_macro(guild_id, "logs", 200).await.unwrap();
+
+    let results = database
+        .search_macros(guild_id, "installer", 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        names(results.iter().map(|result| &result.name)),
+        ["install"]
+    );
+}
+
+async fn import_macros(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "old", "Old"), vec![], vec![])
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "kept", "Kept"), vec![], vec![])
+        .await
+        .unwrap();
+
+    database
+        .import_macros(
+            guild_id,
+            vec![
+                (
+                    new_macro(guild_id, "new", "New {0}"),
+                    vec![attachment("a.png")],
+                    vec!["imported".to_string()],
+                ),
+                (new_macro(guild_id, "kept", "Updated"), vec![], vec![]),
+            ],
+            vec!["old".to_string()],
+            200,
+        )
+        .await
+        .unwrap();
+
+    let macros = database
+        .get_macros_with_attachments(guild_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        macros
+            .iter()
+            .map(|(r#macro, attachments)| (r#macro.name.as_str(), attachments.len()))
+            .collect::<Vec<_>>(),
+        [("kept", 0), ("new", 1)]
+    );
+    assert_eq!(macros[0].0.content, "Updated");
+    assert_eq!(database.get_tags(guild_id).await.unwrap(), ["imported"]);
+
+    let deleted = database.get_deleted_macros(guild_id).await.unwrap();
+    assert_eq!(names(deleted.iter().map(|r#macro| &r#macro.name)), ["old"]);
+}
+
+async fn invocations(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(guild_id, "bye", "Bye"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let (hello, _) = database
+        .get_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    let (bye, _) = database.get_macro(guild_id, "bye").await.unwrap().unwrap();
+
+    for (macro_id, user_id, timestamp) in [
+        (hello.id, "10", 100),
+        (hello.id, "10", 200),
+        (hello.id, "11", 300),
+        (bye.id, "11", 50),
+    ] {
+        database
+            .record_invocation(macro_id, user_id, "1", timestamp, Source::Live)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(database.count_invocations(hello.id, 0).await.unwrap(), 3);
+    assert_eq!(database.count_invocations(hello.id, 200).await.unwrap(), 2);
+
+    assert_eq!(
+        database.get_top_macros(guild_id, 0, 10).await.unwrap(),
+        [("hello".to_string(), 3), ("bye".to_string(), 1)]
+    );
+    assert_eq!(
+        database.get_top_macros(guild_id, 100, 10).await.unwrap(),
+        [("hello".to_string(), 3)]
+    );
+    assert_eq!(
+        database
+            .get_top_invokers(guild_id, Some(hello.id), 0, 10)
+            .await
+            .unwrap(),
+        [("10".to_string(), 2), ("11".to_string(), 1)]
+    );
+}
+
+async fn cached_macros(database: &Database, guild_id: &str) {
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let cached = database
+        .get_cached_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.r#macro.content, "Hello");
+    assert!(database.cache.get(guild_id).is_some());
+    assert!(database
+        .get_cached_macro(guild_id, "missing")
+        .await
+        .unwrap()
+        .is_none());
+
+    // Changes to the macros of a guild invalidate its cache
+    database
+        .create_alias(cached.r#macro.id, "hi")
+        .await
+        .unwrap();
+    assert!(database.cache.get(guild_id).is_none());
+
+    let alias = database
+        .get_cached_macro(guild_id, "hi")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(alias.r#macro.name, "hello");
+
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hi"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let cached = database
+        .get_cached_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.r#macro.content, "Hi");
+
+    database.delete_macro(guild_id, "hello", 200).await.unwrap();
+    assert!(database
+        .get_cached_macro(guild_id, "hello")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Only runs against SQLite, as the PostgreSQL database is shared with the other tests
+#[tokio::test]
+async fn load_cache_counts_macros() {
+    let database = SqliteDatabase::new();
+    let database = &database.database;
+
+    for guild_id in [guild_id(), guild_id()] {
+        database
+            .create_macro(new_macro(&guild_id, "hello", "Hello"), vec![], vec![])
+            .await
+            .unwrap();
+        database
+            .create_macro(new_macro(&guild_id, "bye", "Bye"), vec![], vec![])
+            .await
+            .unwrap();
+
+        let id = database
+            .get_macro(&guild_id, "bye")
+            .await
+            .unwrap()
+            .unwrap()
+            .0
+            .id;
+        database.create_alias(id, "cya").await.unwrap();
+        database.create_alias(id, "later").await.unwrap();
+    }
+
+    assert_eq!(database.load_cache().await.unwrap(), 4);
+}
+
+/// Only runs against SQLite, as the PostgreSQL database is shared with the other tests
+#[tokio::test]
+async fn unscoped_macros() {
+    let database = SqliteDatabase::new();
+    let database = &database.database;
+    let guild_id = guild_id();
+
+    database
+        .create_macro(new_macro("", "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+    database
+        .create_macro(new_macro(&guild_id, "bye", "Bye"), vec![], vec![])
+        .await
+        .unwrap();
+    assert_eq!(database.count_unscoped_macros().await.unwrap(), 1);
+
+    assert_eq!(database.assign_unscoped_macros(&guild_id).await.unwrap(), 1);
+    assert_eq!(database.count_unscoped_macros().await.unwrap(), 0);
+    assert!(database
+        .get_macro(&guild_id, "hello")
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Tests run on a single threaded runtime, which a query that blocks the runtime would stall
+/// completely
+async fn slow_queries_do_not_stall_the_runtime(database: &Database, guild_id: &str) {
+    const ROWS: i64 = 2_000_000;
+
+    database
+        .create_macro(new_macro(guild_id, "hello", "Hello"), vec![], vec![])
+        .await
+        .unwrap();
+
+    let slow = {
+        let database = database.clone();
+
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let row: Count = with_connection!(database, |conn| {
+                // Counting rows one at a time takes around a second on either backend
+                Ok(diesel::sql_query(format!(
+                    "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {ROWS}) \
+                     SELECT count(*) AS count FROM n"
+                ))
+                .get_result(conn)?)
+            })?;
+
+            anyhow::Ok((row.count, start.elapsed()))
+        })
+    };
+
+    // Let the slow query start, then make sure other queries are answered in the meantime
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let macros = database.get_macros(guild_id).await.unwrap();
+    assert_eq!(macros.len(), 1);
+    assert!(!slow.is_finished(), "the query finished too quickly");
+
+    // Timers keep firing on time while the query holds its connection
+    let mut ticks = 0;
+    let mut longest = Duration::ZERO;
+    while !slow.is_finished() {
+        let tick = Instant::now();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        longest = longest.max(tick.elapsed());
+        ticks += 1;
+    }
+
+    let (count, elapsed) = slow.await.unwrap().unwrap();
+    assert_eq!(count, ROWS);
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+    assert!(ticks >= 10, "{ticks} ticks in {elapsed:?}");
+    assert!(longest < Duration::from_millis(200), "{longest:?}");
+}
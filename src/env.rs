@@ -1,6 +1,6 @@
-use std::sync::LazyLock;
+use std::{path::PathBuf, sync::LazyLock};
 
-use poise::serenity_prelude::RoleId;
+use poise::serenity_prelude::{GuildId, RoleId};
 
 pub static DISCORD_TOKEN: LazyLock<String> = LazyLock::new(|| {
     std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN environment variable")
@@ -10,8 +10,56 @@ pub static DATABASE_URL: LazyLock<String> = LazyLock::new(|| {
     std::env::var("DATABASE_URL").expect("missing DATABASE_URL environment variable")
 });
 
-pub static MACRO_ROLE_ID: LazyLock<RoleId> = LazyLock::new(|| {
-    let id = std::env::var("MACRO_ROLE_ID").expect("missing MACRO_ROLE_ID environment variable");
+/// Roles that are allowed to invoke macros, separated by commas to support multiple guilds
+pub static MACRO_ROLE_ID: LazyLock<Vec<RoleId>> = LazyLock::new(|| {
+    let ids = std::env::var("MACRO_ROLE_ID").expect("missing MACRO_ROLE_ID environment variable");
 
-    RoleId::new(id.parse().expect("invalid MACRO_ROLE_ID specified"))
+    ids.split(',')
+        .map(|id| RoleId::new(id.trim().parse().expect("invalid MACRO_ROLE_ID specified")))
+        .collect()
+});
+
+/// The guild that macros created before macros were scoped per guild belong to
+pub static DEFAULT_GUILD_ID: LazyLock<Option<GuildId>> = LazyLock::new(|| {
+    let id = std::env::var("DEFAULT_GUILD_ID").ok()?;
+
+    Some(GuildId::new(
+        id.parse().expect("invalid DEFAULT_GUILD_ID specified"),
+    ))
+});
+
+/// How many days deleted macros are kept in the trash before they are purged
+pub static TRASH_RETENTION_DAYS: LazyLock<i64> = LazyLock::new(|| {
+    std::env::var("TRASH_RETENTION_DAYS").map_or(30, |days| {
+        days.parse()
+            .ok()
+            .filter(|&days| days > 0)
+            .expect("invalid TRASH_RETENTION_DAYS specified")
+    })
+});
+
+/// Where backups of the database are written to, backups are disabled if this isn't specified
+pub static BACKUP_DIR: LazyLock<Option<PathBuf>> =
+    LazyLock::new(|| std::env::var("BACKUP_DIR").ok().map(PathBuf::from));
+
+/// How many hours there are between scheduled backups, at most a year
+pub static BACKUP_INTERVAL_HOURS: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("BACKUP_INTERVAL_HOURS").map_or(24, |hours| {
+        hours
+            .parse()
+            .ok()
+            .filter(|hours| (1..=365 * 24).contains(hours))
+            .expect("invalid BACKUP_INTERVAL_HOURS specified")
+    })
+});
+
+/// How many backups are kept, older backups are removed and at least one is kept
+pub static BACKUP_RETENTION: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("BACKUP_RETENTION").map_or(7, |count| {
+        count
+            .parse()
+            .ok()
+            .filter(|&count| count > 0)
+            .expect("invalid BACKUP_RETENTION specified")
+    })
 });
@@ -1,4 +1,92 @@
+use std::ops::Range;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
-pub enum Error {}
+pub enum Error {
+    #[error(
+        "Missing argument{}: {}",
+        if .0.len() == 1 { "" } else { "s" },
+        .0.iter().map(|name| format!("`{name}`")).collect::<Vec<_>>().join(", ")
+    )]
+    MissingArguments(Vec<String>),
+}
+
+/// Byte range within the macro content that an error refers to
+pub type Span = Range<usize>;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unmatched '{{' found.")]
+    UnmatchedBrace { span: Span },
+
+    #[error("Missing parameter: {{{number}}}")]
+    MissingParameter { number: usize, span: Span },
+
+    #[error("Unknown placeholder: '{{{placeholder}}}'")]
+    UnknownPlaceholder { placeholder: String, span: Span },
+
+    #[error("Unknown filter: '{filter}'")]
+    UnknownFilter { filter: String, span: Span },
+
+    #[error("Unmatched '{block}' found.")]
+    UnmatchedBlock { block: &'static str, span: Span },
+
+    #[error("Unclosed '{block}' found.")]
+    UnclosedBlock { block: &'static str, span: Span },
+
+    #[error("Only one argument may be used with '{{#each}}'.")]
+    MultipleVariadics { span: Span },
+
+    #[error("Built-in placeholders cannot be used with '{{#each}}'.")]
+    VariadicBuiltin { span: Span },
+
+    #[error("Macro contains more parameters than attachments ({required} required, {provided} provided)")]
+    TooFewAttachments {
+        required: usize,
+        provided: usize,
+        span: Span,
+    },
+}
+
+impl TemplateError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnmatchedBrace { span }
+            | Self::MissingParameter { span, .. }
+            | Self::UnknownPlaceholder { span, .. }
+            | Self::UnknownFilter { span, .. }
+            | Self::UnmatchedBlock { span, .. }
+            | Self::UnclosedBlock { span, .. }
+            | Self::MultipleVariadics { span }
+            | Self::VariadicBuiltin { span }
+            | Self::TooFewAttachments { span, .. } => span.clone(),
+        }
+    }
+
+    /// Show the line of `input` that contains the error, with carets underneath the offending part
+    pub fn excerpt(&self, input: &str) -> String {
+        let span = self.span();
+        let start = span.start.min(input.len());
+
+        let line_start = input[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = input[start..]
+            .find('\n')
+            .map_or(input.len(), |index| start + index);
+
+        let line_number = (input[..start].matches('\n').count() + 1).to_string();
+        let column = input[line_start..start].chars().count();
+        let width = input[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        format!(
+            "{line_number} | {}\n{} | {}{}",
+            &input[line_start..line_end],
+            " ".repeat(line_number.len()),
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
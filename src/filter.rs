@@ -0,0 +1,204 @@
+/// A transform that is applied to the value of a placeholder, e.g. `{path|code}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// `upper`, converts the value to uppercase
+    Upper,
+
+    /// `lower`, converts the value to lowercase
+    Lower,
+
+    /// `code`, formats the value as inline code
+    Code,
+
+    /// `spoiler`, hides the value behind a spoiler
+    Spoiler,
+
+    /// `url_encode`, percent-encodes the value so it can be used inside a link
+    UrlEncode,
+
+    /// `truncate:n`, shortens the value to at most `n` characters
+    Truncate(usize),
+}
+
+impl Filter {
+    /// Parse a single filter, either `name` or `name:argument`
+    pub fn parse(input: &str) -> Option<Self> {
+        let (name, argument) = match input.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (input.trim(), None),
+        };
+
+        match (name, argument) {
+            ("upper", None) => Some(Self::Upper),
+            ("lower", None) => Some(Self::Lower),
+            ("code", None) => Some(Self::Code),
+            ("spoiler", None) => Some(Self::Spoiler),
+            ("url_encode", None) => Some(Self::UrlEncode),
+            ("truncate", Some(length)) => length.parse().ok().map(Self::Truncate),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Code => {
+                // Backticks inside the value would end the code span early
+                if value.contains('`') {
+                    format!("`` {} ``", separate(value, '`'))
+                } else {
+                    format!("`{value}`")
+                }
+            }
+            Self::Spoiler => {
+                // Pipes next to each other or to the surrounding ones would end the spoiler early
+                let mut value = separate(value, '|');
+                if value.starts_with('|') {
+                    value.insert(0, '\u{200B}');
+                }
+                if value.ends_with('|') {
+                    value.push('\u{200B}');
+                }
+
+                format!("||{value}||")
+            }
+            Self::UrlEncode => url_encode(value),
+            Self::Truncate(length) => truncate(value, length),
+        }
+    }
+}
+
+/// Put a zero-width space between every two adjacent occurrences of `c`, so that they don't form
+/// markdown syntax
+fn separate(value: &str, c: char) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut previous = None;
+
+    for current in value.chars() {
+        if current == c && previous == Some(c) {
+            result.push('\u{200B}');
+        }
+
+        result.push(current);
+        previous = Some(current);
+    }
+
+    result
+}
+
+fn url_encode(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result += &format!("%{byte:02X}"),
+        }
+    }
+
+    result
+}
+
+/// Cut the value off at `length` characters, the last of which becomes an ellipsis
+fn truncate(value: &str, length: usize) -> String {
+    if value.chars().count() <= length {
+        return value.to_string();
+    }
+
+    let mut result = value
+        .chars()
+        .take(length.saturating_sub(1))
+        .collect::<String>();
+
+    if length > 0 {
+        result.push('…');
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters() {
+        assert_eq!(Filter::parse("upper"), Some(Filter::Upper));
+        assert_eq!(Filter::parse(" lower "), Some(Filter::Lower));
+        assert_eq!(Filter::parse("code"), Some(Filter::Code));
+        assert_eq!(Filter::parse("spoiler"), Some(Filter::Spoiler));
+        assert_eq!(Filter::parse("url_encode"), Some(Filter::UrlEncode));
+        assert_eq!(Filter::parse("truncate: 10"), Some(Filter::Truncate(10)));
+        assert_eq!(Filter::parse("truncate:0"), Some(Filter::Truncate(0)));
+    }
+
+    #[test]
+    fn parse_invalid_filters() {
+        assert_eq!(Filter::parse(""), None);
+        assert_eq!(Filter::parse("reverse"), None);
+        assert_eq!(Filter::parse("UPPER"), None);
+        assert_eq!(Filter::parse("upper:1"), None);
+        assert_eq!(Filter::parse("truncate"), None);
+        assert_eq!(Filter::parse("truncate:"), None);
+        assert_eq!(Filter::parse("truncate:-1"), None);
+        assert_eq!(Filter::parse("truncate:ten"), None);
+    }
+
+    #[test]
+    fn change_case() {
+        assert_eq!(Filter::Upper.apply("Straße"), "STRASSE");
+        assert_eq!(Filter::Lower.apply("ÄÖÜ Text"), "äöü text");
+    }
+
+    #[test]
+    fn truncate_to_zero() {
+        assert_eq!(Filter::Truncate(0).apply("value"), "");
+        assert_eq!(Filter::Truncate(0).apply(""), "");
+    }
+
+    #[test]
+    fn truncate_counts_characters() {
+        assert_eq!(Filter::Truncate(5).apply("héllo"), "héllo");
+        assert_eq!(Filter::Truncate(5).apply("héllo wörld"), "héll…");
+        assert_eq!(Filter::Truncate(2).apply("👍👍👍"), "👍…");
+        assert_eq!(Filter::Truncate(1).apply("日本語"), "…");
+    }
+
+    #[test]
+    fn code_without_backticks() {
+        assert_eq!(Filter::Code.apply("C:\\Games"), "`C:\\Games`");
+    }
+
+    #[test]
+    fn code_with_backticks() {
+        assert_eq!(Filter::Code.apply("a`b"), "`` a`b ``");
+        assert_eq!(Filter::Code.apply("`a`"), "`` `a` ``");
+        assert_eq!(Filter::Code.apply("a```b"), "`` a`\u{200B}`\u{200B}`b ``");
+    }
+
+    #[test]
+    fn spoiler_with_pipes() {
+        assert_eq!(Filter::Spoiler.apply("secret"), "||secret||");
+        assert_eq!(Filter::Spoiler.apply("a|b"), "||a|b||");
+        assert_eq!(Filter::Spoiler.apply("a||b"), "||a|\u{200B}|b||");
+        assert_eq!(
+            Filter::Spoiler.apply("|a|||"),
+            "||\u{200B}|a|\u{200B}|\u{200B}|\u{200B}||"
+        );
+    }
+
+    #[test]
+    fn url_encode_non_ascii() {
+        assert_eq!(Filter::UrlEncode.apply("safe-_.~09AZaz"), "safe-_.~09AZaz");
+        assert_eq!(Filter::UrlEncode.apply("crash log?"), "crash%20log%3F");
+        assert_eq!(
+            Filter::UrlEncode.apply("héllo wörld"),
+            "h%C3%A9llo%20w%C3%B6rld"
+        );
+        assert_eq!(Filter::UrlEncode.apply("日本"), "%E6%97%A5%E6%9C%AC");
+        assert_eq!(Filter::UrlEncode.apply("👍"), "%F0%9F%91%8D");
+    }
+}
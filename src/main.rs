@@ -1,15 +1,23 @@
+mod backup;
 mod commands;
 mod database;
 mod env;
 mod error;
+mod filter;
 mod params;
+mod render;
+mod split;
 
-use std::sync::Arc;
+use std::{
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 
 use anyhow::{Error, Result};
 use database::Database;
-use log::{error, info};
-use poise::serenity_prelude::{self as serenity, FullEvent, ShardManager};
+use log::{error, info, warn};
+use poise::serenity_prelude::{self as serenity, FullEvent, ShardManager, Timestamp};
 
 type Context<'a> = poise::ApplicationContext<'a, Database, Error>;
 
@@ -27,14 +35,43 @@ async fn main() -> Result<()> {
     env_logger::init();
     dotenvy::dotenv().ok();
 
+    // Settings that are only used later on are read now, so that invalid values stop the bot
+    // right away
+    LazyLock::force(&env::TRASH_RETENTION_DAYS);
+    LazyLock::force(&env::BACKUP_DIR);
+    LazyLock::force(&env::BACKUP_INTERVAL_HOURS);
+    LazyLock::force(&env::BACKUP_RETENTION);
+
     let database = Database::connect(&*env::DATABASE_URL)?;
 
+    if let Some(guild_id) = *env::DEFAULT_GUILD_ID {
+        let count = database
+            .assign_unscoped_macros(&guild_id.to_string())
+            .await?;
+
+        if count > 0 {
+            info!("Moved {count} macro(s) into the default guild {guild_id}");
+        }
+    } else {
+        let count = database.count_unscoped_macros().await?;
+
+        if count > 0 {
+            warn!("{count} macro(s) don't belong to a guild and can't be used, set DEFAULT_GUILD_ID to move them into a guild");
+        }
+    }
+
+    let count = database.load_cache().await?;
+    info!("Loaded {count} macro(s) into the cache");
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             commands: vec![
                 commands::add_macro(),
+                commands::add_variant(),
+                commands::admin(),
                 commands::delete(),
                 commands::macros(),
+                commands::macro_(),
             ],
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
@@ -45,6 +82,15 @@ async fn main() -> Result<()> {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 tokio::spawn(shutdown_handler(framework.shard_manager().clone()));
+                tokio::spawn(purge_handler(database.clone()));
+
+                if let Some(ref dir) = *env::BACKUP_DIR {
+                    if database.supports_backups() {
+                        tokio::spawn(backup_handler(database.clone(), dir.clone()));
+                    } else {
+                        warn!("Scheduled backups are disabled, only SQLite databases can be backed up");
+                    }
+                }
 
                 Ok(database)
             })
@@ -79,7 +125,11 @@ async fn event_handler(
             return Ok(());
         };
 
-        if !member.roles.contains(&env::MACRO_ROLE_ID) {
+        if !member
+            .roles
+            .iter()
+            .any(|role| env::MACRO_ROLE_ID.contains(role))
+        {
             return Ok(());
         }
 
@@ -95,6 +145,40 @@ async fn event_handler(
     Ok(())
 }
 
+/// Periodically delete macros that have been in the trash for longer than the retention period
+async fn purge_handler(database: Database) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        let before = Timestamp::now()
+            .unix_timestamp()
+            .saturating_sub(env::TRASH_RETENTION_DAYS.saturating_mul(24 * 60 * 60));
+
+        match database.purge_deleted_macros(before).await {
+            Ok(0) => {}
+            Ok(count) => info!("Purged {count} deleted macro(s)"),
+            Err(why) => error!("Failed to purge deleted macros: {why}"),
+        }
+    }
+}
+
+/// Periodically write a backup of the database to `dir`
+async fn backup_handler(database: Database, dir: PathBuf) {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(*env::BACKUP_INTERVAL_HOURS * 60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        match backup::create_backup(&database, &dir, *env::BACKUP_RETENTION).await {
+            Ok(path) => info!("Backed up database to {}", path.display()),
+            Err(why) => error!("Failed to back up database: {why}"),
+        }
+    }
+}
+
 async fn shutdown_handler(shard_manager: Arc<ShardManager>) {
     _ = tokio::signal::ctrl_c().await;
 
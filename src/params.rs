@@ -1,98 +1,770 @@
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 
-use anyhow::{anyhow, Result};
 use indexmap::IndexMap;
 
-type Parameters = IndexMap<(usize, usize), usize>;
+use crate::{
+    error::{Error, Span, TemplateError},
+    filter::Filter,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter<'a> {
+    /// A numeric placeholder (`{0}`) which is filled with an attachment url, or one of its
+    /// properties (`{0.filename}`)
+    Attachment(usize, Property),
+
+    /// A named placeholder (`{version}`) which is filled with an argument from the invoker
+    ///
+    /// Optional placeholders (`{version?}`) fall back to an empty string, placeholders with a
+    /// default (`{version:latest}`) fall back to the default.
+    Argument {
+        name: &'a str,
+        default: Option<&'a str>,
+    },
+
+    /// A built-in placeholder (`{author}`) which is filled from the invoking message
+    Variable(Variable),
+
+    /// An inclusion (`{macro:name}`) which is filled with the rendered content of another macro
+    Include(&'a str),
+
+    /// A random choice (`{random:a|b|c}`) which is filled with one of the options, which are kept
+    /// unsplit here
+    Random(&'a str),
+}
+
+/// The part of an attachment that a numeric placeholder is filled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Url,
+    Filename,
+    Size,
+    ContentType,
+    Spoiler,
+}
+
+impl Property {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "url" => Some(Self::Url),
+            "filename" => Some(Self::Filename),
+            "size" => Some(Self::Size),
+            "content_type" => Some(Self::ContentType),
+            "spoiler" => Some(Self::Spoiler),
+            _ => None,
+        }
+    }
+}
+
+/// Built-in placeholders, their names cannot be used for arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// Mention of the person who invoked the macro
+    Author,
+
+    /// Mention of the author of the message the invoker replied to
+    Replied,
+
+    /// Mention of the channel the macro was invoked in
+    Channel,
+
+    /// Name of the server the macro was invoked in
+    Guild,
+
+    /// Time of invocation as a Discord timestamp, optionally with a style (`{now:R}`)
+    Now(Option<char>),
+}
+
+impl Variable {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "author" => Some(Self::Author),
+            "replied" => Some(Self::Replied),
+            "channel" => Some(Self::Channel),
+            "guild" => Some(Self::Guild),
+            "now" => Some(Self::Now(None)),
+            _ => None,
+        }
+    }
+}
+
+/// A condition of an `{#if}` block, either `{#if name}` or `{#if name=value}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition<'a> {
+    pub name: &'a str,
+    pub value: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node<'a> {
+    /// Literal text that is copied to the output as-is
+    Text(&'a str),
+
+    /// A placeholder that is replaced during rendering, followed by the filters applied to its value
+    Parameter(Parameter<'a>, Vec<Filter>),
+
+    /// `{#if condition}...{#else}...{/if}`
+    If {
+        condition: Condition<'a>,
+        then: Vec<Node<'a>>,
+        otherwise: Vec<Node<'a>>,
+    },
+
+    /// `{#each name}...{/each}`, repeated for every value of a variadic argument
+    Each { name: &'a str, body: Vec<Node<'a>> },
+}
+
+/// A block that has been opened but not yet closed while parsing
+enum Block<'a> {
+    If {
+        condition: Condition<'a>,
+        then: Option<Vec<Node<'a>>>,
+    },
+    Each {
+        name: &'a str,
+    },
+}
 
 #[derive(Debug)]
 pub struct ParameterizedString<'a> {
-    input: &'a str,
-    parameters: Parameters,
-    count: usize,
+    nodes: Vec<Node<'a>>,
+    /// Location of the first placeholder for every attachment, indexed by attachment number
+    attachments: Vec<Span>,
+    /// Named arguments in order of first appearance, and whether they must be supplied
+    arguments: IndexMap<&'a str, bool>,
+    /// The argument that is looped over with `{#each}`, which receives all remaining arguments
+    variadic: Option<&'a str>,
+    /// Location of numeric placeholders inside code blocks, which are left untouched
+    raw_attachments: Vec<Span>,
 }
 
 impl<'a> ParameterizedString<'a> {
-    pub fn new(input: &'a str) -> Result<Self> {
-        let (parameters, count) = Self::extract_parameters(input)?;
-
-        Ok(Self {
-            input,
-            parameters,
-            count,
-        })
-    }
-
-    pub fn parameters(&self) -> usize {
-        self.count
-    }
-
-    fn extract_parameters(input: &str) -> Result<(Parameters, usize)> {
-        let mut parameters = IndexMap::new();
-        let mut set = HashSet::new();
-        let mut min = 0;
-        let mut max = 0;
-
-        let mut start = 0;
-        while let Some(open_brace) = input[start..].find('{') {
-            let start_index = start + open_brace;
-            if let Some(close_brace) = input[start_index..].find('}') {
-                let end_index = start_index + close_brace;
-                let number_str = &input[start_index + 1..end_index];
-
-                match number_str.parse::<usize>() {
-                    Ok(number) => {
-                        parameters.insert((start_index, end_index), number);
-                        set.insert(number);
-                        min = min.min(number);
-                        max = max.max(number);
+    pub fn new(input: &'a str) -> Result<Self, TemplateError> {
+        let mut result = Self {
+            nodes: vec![],
+            attachments: vec![],
+            arguments: IndexMap::new(),
+            variadic: None,
+            raw_attachments: vec![],
+        };
+
+        result.extract_parameters(input)?;
+
+        Ok(result)
+    }
+
+    /// The amount of attachments that are required to fill all numeric placeholders
+    pub fn attachments(&self) -> usize {
+        self.attachments.len()
+    }
+
+    /// Verify that `count` attachments are enough to fill all numeric placeholders
+    pub fn check_attachments(&self, count: usize) -> Result<(), TemplateError> {
+        match self.attachments.get(count) {
+            Some(span) => Err(TemplateError::TooFewAttachments {
+                required: self.attachments.len(),
+                provided: count,
+                span: span.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Numeric placeholders inside code blocks, these were replaced before code blocks were left
+    /// untouched, so older macros may still expect them to be
+    pub fn raw_attachments(&self) -> &[Span] {
+        &self.raw_attachments
+    }
+
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+
+    /// The position of a named argument in the invoker's argument list
+    pub fn argument_index(&self, name: &str) -> Option<usize> {
+        self.arguments.get_index_of(name)
+    }
+
+    /// The names of the arguments, excluding the variadic argument, in positional order
+    pub fn argument_names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.arguments.keys().copied()
+    }
+
+    /// The variadic argument and the position at which its values start
+    pub fn variadic(&self) -> Option<(&'a str, usize)> {
+        self.variadic.map(|name| (name, self.arguments.len()))
+    }
+
+    /// Build a usage hint for the arguments of this string, e.g. ` <user> [version] [files...]`
+    pub fn usage(&self) -> String {
+        let mut usage = self
+            .arguments
+            .iter()
+            .map(|(name, &required)| {
+                if required {
+                    format!(" <{name}>")
+                } else {
+                    format!(" [{name}]")
+                }
+            })
+            .collect::<String>();
+
+        if let Some(name) = self.variadic {
+            usage += &format!(" [{name}...]");
+        }
+
+        usage
+    }
+
+    /// Parse the input into a tree of literal text, placeholders and blocks
+    ///
+    /// `{{` and `}}` produce a literal brace, and anything inside a triple-backtick code block is
+    /// left untouched so that code snippets and config files don't need to be escaped.
+    fn extract_parameters(&mut self, input: &'a str) -> Result<(), TemplateError> {
+        let mut attachments = BTreeMap::<usize, Span>::new();
+
+        // Nodes of the innermost open block, the nodes of enclosing blocks are kept on the stack
+        // together with the location of the placeholder that opened the block
+        let mut nodes = vec![];
+        let mut stack: Vec<(Block, Span, Vec<Node>)> = vec![];
+
+        let mut text_start = 0;
+        let mut index = 0;
+        while index < input.len() {
+            let rest = &input[index..];
+
+            if let Some(code) = rest.strip_prefix("```") {
+                // Skip over the raw region, an unterminated code block runs until the end of the input
+                let end = code.find("```");
+
+                self.raw_attachments.extend(attachment_spans(
+                    &code[..end.unwrap_or(code.len())],
+                    index + 3,
+                ));
+
+                index = match end {
+                    Some(end) => index + 3 + end + 3,
+                    None => input.len(),
+                };
+            } else if rest.starts_with("{{") || rest.starts_with("}}") {
+                // Keep the first brace, drop the second
+                nodes.push(Node::Text(&input[text_start..index + 1]));
+                index += 2;
+                text_start = index;
+            } else if rest.starts_with('{') {
+                // Placeholders can't span lines or code blocks, otherwise a stray brace would pair
+                // up with one further down and swallow everything in between
+                let line_end = [rest.find('\n'), rest.find("```")]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                    .unwrap_or(rest.len());
+
+                let Some(close_brace) = rest[..line_end].find('}') else {
+                    return Err(TemplateError::UnmatchedBrace {
+                        span: index..index + 1,
+                    });
+                };
+
+                let end_index = index + close_brace;
+                let span = index..end_index + 1;
+                let name = &input[index + 1..end_index];
+                let text = Node::Text(&input[text_start..index]);
+
+                // Filters are separated from the placeholder by pipes, `{path|code}`, except for
+                // random choices which use pipes to separate their options
+                let (head, filters) = match name.split_once('|') {
+                    Some((head, filters)) if !name.starts_with("random:") => (head, Some(filters)),
+                    _ => (name, None),
+                };
+
+                let parameter = if let Some(options) = head.strip_prefix("random:") {
+                    Some(Parameter::Random(options))
+                } else if let Some((number, property)) = parse_attachment(head) {
+                    let Some(property) = property else {
+                        return Err(TemplateError::UnknownPlaceholder {
+                            placeholder: head.to_string(),
+                            span,
+                        });
+                    };
+
+                    attachments.entry(number).or_insert(span.clone());
+
+                    Some(Parameter::Attachment(number, property))
+                } else if let Some(name) = parse_include(head) {
+                    Some(Parameter::Include(name))
+                } else if let Some(variable) = parse_variable(head) {
+                    Some(Parameter::Variable(variable))
+                } else if let Some((name, default)) = parse_argument(head) {
+                    // An argument is required if any of its placeholders lacks a fallback and
+                    // isn't guarded by an `{#if}` block checking for its presence
+                    let guarded = stack.iter().any(|(block, _, _)| {
+                        matches!(block, Block::If { condition, .. } if condition.name == name)
+                    });
+
+                    *self.arguments.entry(name).or_default() |= default.is_none() && !guarded;
+
+                    Some(Parameter::Argument { name, default })
+                } else {
+                    None
+                };
+
+                if let Some(parameter) = parameter {
+                    let filters = filters
+                        .into_iter()
+                        .flat_map(|filters| filters.split('|'))
+                        .map(|filter| {
+                            Filter::parse(filter).ok_or_else(|| TemplateError::UnknownFilter {
+                                filter: filter.trim().to_string(),
+                                span: span.clone(),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    nodes.push(text);
+                    nodes.push(Node::Parameter(parameter, filters));
+                    text_start = end_index + 1;
+                } else if let Some(directive) = parse_directive(name) {
+                    nodes.push(text);
+                    text_start = end_index + 1;
+
+                    match directive {
+                        Directive::If(condition) => {
+                            if Variable::from_name(condition.name).is_none() {
+                                self.arguments.entry(condition.name).or_default();
+                            }
+
+                            let block = Block::If {
+                                condition,
+                                then: None,
+                            };
+                            stack.push((block, span, std::mem::take(&mut nodes)));
+                        }
+                        Directive::Else => match stack.last_mut() {
+                            Some((
+                                Block::If {
+                                    then: then @ None, ..
+                                },
+                                _,
+                                _,
+                            )) => {
+                                *then = Some(std::mem::take(&mut nodes));
+                            }
+                            _ => {
+                                return Err(TemplateError::UnmatchedBlock {
+                                    block: "{#else}",
+                                    span,
+                                })
+                            }
+                        },
+                        Directive::Each(name) => {
+                            if Variable::from_name(name).is_some() {
+                                return Err(TemplateError::VariadicBuiltin { span });
+                            }
+
+                            if self.variadic.is_some_and(|variadic| variadic != name) {
+                                return Err(TemplateError::MultipleVariadics { span });
+                            }
+
+                            self.variadic = Some(name);
+                            stack.push((Block::Each { name }, span, std::mem::take(&mut nodes)));
+                        }
+                        Directive::EndIf => match stack.pop() {
+                            Some((Block::If { condition, then }, _, parent)) => {
+                                let block = std::mem::replace(&mut nodes, parent);
+                                let (then, otherwise) = match then {
+                                    Some(then) => (then, block),
+                                    None => (block, vec![]),
+                                };
+
+                                nodes.push(Node::If {
+                                    condition,
+                                    then,
+                                    otherwise,
+                                });
+                            }
+                            _ => {
+                                return Err(TemplateError::UnmatchedBlock {
+                                    block: "{/if}",
+                                    span,
+                                })
+                            }
+                        },
+                        Directive::EndEach => match stack.pop() {
+                            Some((Block::Each { name }, _, parent)) => {
+                                let body = std::mem::replace(&mut nodes, parent);
+
+                                nodes.push(Node::Each { name, body });
+                            }
+                            _ => {
+                                return Err(TemplateError::UnmatchedBlock {
+                                    block: "{/each}",
+                                    span,
+                                })
+                            }
+                        },
                     }
-                    Err(_) => {}
+                } else if is_reserved_syntax(name) {
+                    return Err(TemplateError::UnknownPlaceholder {
+                        placeholder: name.to_string(),
+                        span,
+                    });
                 }
 
-                start = end_index + 1;
+                index = end_index + 1;
             } else {
-                return Err(anyhow!("Unmatched '{{' found."));
+                index += rest.chars().next().map_or(1, char::len_utf8);
             }
         }
 
-        if !set.is_empty() {
-            for num in min..=max {
-                if !set.contains(&num) {
-                    return Err(anyhow!("Missing parameter: {{{num}}}"));
-                }
+        nodes.push(Node::Text(&input[text_start..]));
+
+        if let Some((block, span, _)) = stack.pop() {
+            return Err(TemplateError::UnclosedBlock {
+                block: match block {
+                    Block::If { .. } => "{#if}",
+                    Block::Each { .. } => "{#each}",
+                },
+                span,
+            });
+        }
+
+        // Attachment numbers must be contiguous, starting at zero
+        for (expected, (&number, span)) in attachments.iter().enumerate() {
+            if number != expected {
+                return Err(TemplateError::MissingParameter {
+                    number: expected,
+                    span: span.clone(),
+                });
             }
         }
 
-        Ok((parameters, set.len()))
-    }
-
-    pub fn to_string<S>(&self, parameters: Vec<S>) -> Result<String>
-    where
-        S: AsRef<str>,
-    {
-        if parameters.len() != self.count {
-            return Err(anyhow!(
-                "Expected {} parameter{}, got {} parameter{}",
-                self.parameters.len(),
-                if self.parameters.len() == 1 { "" } else { "s" },
-                parameters.len(),
-                if parameters.len() == 1 { "" } else { "s" }
-            ));
+        // The variadic argument always comes last, regardless of where it first appeared
+        if let Some(name) = self.variadic {
+            self.arguments.shift_remove(name);
         }
 
-        let mut result = String::new();
+        self.nodes = nodes;
+        self.attachments = attachments.into_values().collect();
 
-        let mut index = 0;
-        for (&(start_idx, end_idx), &i) in &self.parameters {
-            result += &self.input[index..start_idx];
-            result += parameters[i].as_ref();
+        Ok(())
+    }
 
-            index = end_idx + 1;
+    /// Split the text an invoker typed after the macro name into this string's arguments
+    ///
+    /// Arguments are separated by whitespace and may be wrapped in double quotes to include spaces.
+    /// The last argument receives the remainder of the input verbatim, without the quotes if it's
+    /// quoted as a whole, unless there is a variadic argument, in which case the remainder is split
+    /// up as well.
+    pub fn split_arguments<'b>(&self, input: &'b str) -> Vec<&'b str> {
+        let mut result = Vec::new();
+        let mut rest = input.trim();
+
+        while !rest.is_empty() && (self.variadic.is_some() || result.len() < self.arguments.len()) {
+            if self.variadic.is_none() && result.len() == self.arguments.len() - 1 {
+                let unquoted = rest
+                    .strip_prefix('"')
+                    .and_then(|quoted| quoted.strip_suffix('"'))
+                    .filter(|quoted| !quoted.contains('"'));
+
+                result.push(unquoted.unwrap_or(rest));
+                break;
+            }
+
+            let (argument, remainder) = match rest.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+            };
+
+            result.push(argument);
+            rest = remainder.trim_start();
         }
 
-        result += &self.input[index..];
+        result
+    }
 
-        Ok(result)
+    /// Verify that enough arguments were supplied to fill every required named placeholder
+    pub fn check_arguments<S>(&self, arguments: &[S]) -> Result<(), Error> {
+        let missing = self
+            .arguments
+            .iter()
+            .skip(arguments.len())
+            .filter(|(_, &required)| required)
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MissingArguments(missing))
+        }
+    }
+}
+
+/// Parse the inside of a numeric placeholder: `0` or `0.property`, the property is `None` if it
+/// isn't a known property
+fn parse_attachment(input: &str) -> Option<(usize, Option<Property>)> {
+    match input.split_once('.') {
+        Some((number, property)) => Some((number.parse().ok()?, Property::from_name(property))),
+        None => Some((input.parse().ok()?, Some(Property::Url))),
+    }
+}
+
+/// Locate the numeric placeholders in `input`, which starts at `offset` in the macro content
+fn attachment_spans(input: &str, offset: usize) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut start = 0;
+
+    while let Some(open) = input[start..].find('{').map(|open| start + open) {
+        let Some(close) = input[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+
+        if let Some((_, Some(_))) = parse_attachment(&input[open + 1..close]) {
+            spans.push(offset + open..offset + close + 1);
+        }
+
+        start = open + 1;
+    }
+
+    spans
+}
+
+/// Parse the inside of an inclusion placeholder: `macro:name`
+fn parse_include(input: &str) -> Option<&str> {
+    let name = input.strip_prefix("macro:")?;
+
+    (!name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c == '-' || c == '_'))
+    .then_some(name)
+}
+
+/// Parse the inside of a built-in placeholder: `author`, `now` or `now:style`
+fn parse_variable(input: &str) -> Option<Variable> {
+    let Some(style) = input.strip_prefix("now:") else {
+        return Variable::from_name(input);
+    };
+
+    match style {
+        "t" | "T" | "d" | "D" | "f" | "F" | "R" => Some(Variable::Now(style.chars().next())),
+        _ => None,
+    }
+}
+
+/// Parse the inside of a named placeholder: `name`, `name?` or `name:default`
+fn parse_argument(input: &str) -> Option<(&str, Option<&str>)> {
+    let (name, default) = match input.split_once(':') {
+        Some((name, default)) => (name, Some(default)),
+        None => match input.strip_suffix('?') {
+            Some(name) => (name, Some("")),
+            None => (input, None),
+        },
+    };
+
+    (is_argument_name(name)
+        && !matches!(name, "macro" | "random")
+        && Variable::from_name(name).is_none())
+    .then_some((name, default))
+}
+
+enum Directive<'a> {
+    If(Condition<'a>),
+    Else,
+    Each(&'a str),
+    EndIf,
+    EndEach,
+}
+
+/// Parse the inside of a block placeholder: `#if name`, `#if name=value`, `#else`, `#each name`,
+/// `/if` or `/each`
+fn parse_directive(input: &str) -> Option<Directive<'_>> {
+    match input.trim() {
+        "#else" => return Some(Directive::Else),
+        "/if" => return Some(Directive::EndIf),
+        "/each" => return Some(Directive::EndEach),
+        _ => {}
+    }
+
+    if let Some(condition) = input.strip_prefix("#if ") {
+        let (name, value) = match condition.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (condition.trim(), None),
+        };
+
+        return is_argument_name(name).then_some(Directive::If(Condition { name, value }));
+    }
+
+    let name = input.strip_prefix("#each ")?.trim();
+
+    is_argument_name(name).then_some(Directive::Each(name))
+}
+
+/// Placeholders that look like a directive or an inclusion, but aren't valid
+fn is_reserved_syntax(input: &str) -> bool {
+    input.starts_with('#')
+        || input.starts_with('/')
+        || input.starts_with("macro:")
+        || input.starts_with("now:")
+}
+
+/// Argument names start with a lowercase letter or underscore, followed by lowercase letters, digits, `-` or `_`
+fn is_argument_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::models::AttachmentInfo,
+        render::{Renderer, Variables},
+    };
+
+    /// Parse and render `input` with the given arguments and a single attachment
+    fn render(input: &str, arguments: &[&str]) -> String {
+        let attachments = [AttachmentInfo {
+            link: "https://example.com/file.png".to_string(),
+            filename: "file.png".to_string(),
+            size: 1,
+            content_type: None,
+        }];
+        let variables = Variables {
+            author: "<@1>".to_string(),
+            replied: None,
+            channel: "<#2>".to_string(),
+            guild: None,
+            timestamp: 0,
+        };
+
+        let template = ParameterizedString::new(input).unwrap();
+
+        Renderer::new(&attachments, arguments, &variables)
+            .render(&template)
+            .unwrap()
+    }
+
+    fn split(input: &str, arguments: &str) -> Vec<String> {
+        ParameterizedString::new(input)
+            .unwrap()
+            .split_arguments(arguments)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn split_arguments_by_whitespace() {
+        assert_eq!(split("{a} {b}", "one  two"), ["one", "two"]);
+        assert_eq!(split("{a} {b}", " one "), ["one"]);
+        assert_eq!(split("{a} {b}", ""), Vec::<String>::new());
+        assert_eq!(split("Hello", "one two"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn last_argument_takes_the_rest() {
+        assert_eq!(split("{a} {b}", "one two three"), ["one", "two three"]);
+        assert_eq!(split("Hi {name}", "John Smith"), ["John Smith"]);
+        assert_eq!(split("Hi {name}", "  John\nSmith "), ["John\nSmith"]);
+    }
+
+    #[test]
+    fn quoted_arguments() {
+        assert_eq!(split("{a} {b}", r#""one two" three"#), ["one two", "three"]);
+        assert_eq!(split("Hi {name}", r#""John Smith""#), ["John Smith"]);
+        assert_eq!(split("{a} {b}", r#"one "two three""#), ["one", "two three"]);
+        assert_eq!(split("{a} {b}", r#""unterminated"#), ["unterminated"]);
+        assert_eq!(split("{a} {b} {c}", r#""one"#), ["one"]);
+    }
+
+    #[test]
+    fn partially_quoted_rest_is_kept_verbatim() {
+        assert_eq!(
+            split("Hi {name}", r#""John" and "Jane""#),
+            [r#""John" and "Jane""#]
+        );
+        assert_eq!(split("Hi {name}", r#"the "best""#), [r#"the "best""#]);
+        assert_eq!(split("Hi {name}", r#"""#), [r#"""#]);
+    }
+
+    #[test]
+    fn variadic_arguments() {
+        assert_eq!(
+            split("{#each items}{items}{/each}", r#"a "b c" d"#),
+            ["a", "b c", "d"]
+        );
+        assert_eq!(
+            split("{first} {#each rest}{rest}{/each}", "a b c"),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn escaped_braces_render_as_literal_braces() {
+        assert_eq!(render("{{}}", &[]), "{}");
+        assert_eq!(render(r#"{{"key": "value"}}"#, &[]), r#"{"key": "value"}"#);
+        assert_eq!(render("{{{name}}}", &["value"]), "{value}");
+        assert_eq!(render("}}{0}{{", &[]), "}https://example.com/file.png{");
+    }
+
+    #[test]
+    fn unmatched_braces_are_rejected_outside_code_blocks() {
+        assert!(matches!(
+            ParameterizedString::new("a { b"),
+            Err(TemplateError::UnmatchedBrace { span }) if span == (2..3)
+        ));
+    }
+
+    #[test]
+    fn stray_braces_do_not_reach_into_code_blocks() {
+        let input = "Open the file with a { in it\n```json\n{\"key\": 1}\n```\nThen type {name}";
+
+        assert!(matches!(
+            ParameterizedString::new(input),
+            Err(TemplateError::UnmatchedBrace { span }) if span == (21..22)
+        ));
+        assert!(matches!(
+            ParameterizedString::new("a {```}```"),
+            Err(TemplateError::UnmatchedBrace { span }) if span == (2..3)
+        ));
+        assert!(matches!(
+            ParameterizedString::new("a {name\n}"),
+            Err(TemplateError::UnmatchedBrace { span }) if span == (2..3)
+        ));
+    }
+
+    #[test]
+    fn unterminated_code_block_runs_until_the_end() {
+        let input = "{name}\n```json\n{ \"unterminated\": {name}";
+
+        assert_eq!(
+            render(input, &["value"]),
+            "value\n```json\n{ \"unterminated\": {name}"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_code_blocks_are_left_untouched() {
+        let input = "```{name} {0} {{```{name} {0}```{1}```";
+
+        assert_eq!(
+            render(input, &["value"]),
+            "```{name} {0} {{```value https://example.com/file.png```{1}```"
+        );
+    }
+
+    #[test]
+    fn numeric_placeholders_in_code_blocks_are_reported() {
+        let template = ParameterizedString::new("{0} ```{0.filename} {name}``` ```{1}").unwrap();
+
+        assert_eq!(template.raw_attachments(), [7..19, 33..36]);
+        assert_eq!(template.attachments(), 1);
     }
 }
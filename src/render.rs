@@ -0,0 +1,401 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+
+use crate::{
+    database::{
+        models::{Attachment, AttachmentInfo},
+        CachedMacro, Database,
+    },
+    params::{Condition, Node, Parameter, ParameterizedString, Property, Variable},
+};
+
+/// How deep `{macro:name}` inclusions may be nested
+const MAX_INCLUDE_DEPTH: usize = 5;
+
+/// Macros that `{macro:name}` inclusions can refer to by name, along with their attachments
+pub type Includes = HashMap<String, Arc<CachedMacro>>;
+
+/// Look up every macro that `template` can include, directly or through other inclusions, so that
+/// rendering doesn't have to wait for the cache
+///
+/// Macros that don't exist are left out, rendering fails once it reaches them.
+pub async fn load_includes(
+    database: &Database,
+    guild_id: &str,
+    template: &ParameterizedString<'_>,
+) -> Result<Includes> {
+    let mut includes = Includes::new();
+    let mut seen = HashSet::new();
+    let mut pending = VecDeque::new();
+
+    for name in include_names(template.nodes()) {
+        if seen.insert(name.to_string()) {
+            pending.push_back((name.to_string(), 1));
+        }
+    }
+
+    // Breadth first, so that every macro is reached at the shallowest depth it is included at
+    while let Some((name, depth)) = pending.pop_front() {
+        let Some(cached) = database.get_cached_macro(guild_id, &name).await? else {
+            continue;
+        };
+
+        if depth < MAX_INCLUDE_DEPTH {
+            // Templates that don't parse fail once they are rendered
+            if let Ok(included) = cached.template() {
+                for name in include_names(included.nodes()) {
+                    if seen.insert(name.to_string()) {
+                        pending.push_back((name.to_string(), depth + 1));
+                    }
+                }
+            }
+        }
+
+        includes.insert(name, cached);
+    }
+
+    Ok(includes)
+}
+
+/// The names of the macros that are included by `nodes`, including those in blocks
+fn include_names<'a>(nodes: &[Node<'a>]) -> Vec<&'a str> {
+    let mut names = vec![];
+
+    for node in nodes {
+        match node {
+            Node::Parameter(Parameter::Include(name), _) => names.push(*name),
+            Node::If {
+                then, otherwise, ..
+            } => {
+                names.extend(include_names(then));
+                names.extend(include_names(otherwise));
+            }
+            Node::Each { body, .. } => names.extend(include_names(body)),
+            _ => {}
+        }
+    }
+
+    names
+}
+
+/// Values for the built-in placeholders, taken from the message that invoked a macro
+#[derive(Debug, Default)]
+pub struct Variables {
+    pub author: String,
+    pub replied: Option<String>,
+    pub channel: String,
+    pub guild: Option<String>,
+    pub timestamp: i64,
+}
+
+impl Variables {
+    fn get(&self, variable: Variable) -> Option<Cow<'_, str>> {
+        match variable {
+            Variable::Author => Some(Cow::Borrowed(&self.author)),
+            Variable::Replied => self.replied.as_deref().map(Cow::Borrowed),
+            Variable::Channel => Some(Cow::Borrowed(&self.channel)),
+            Variable::Guild => self.guild.as_deref().map(Cow::Borrowed),
+            Variable::Now(style) => Some(Cow::Owned(format!(
+                "<t:{}:{}>",
+                self.timestamp,
+                style.unwrap_or('f')
+            ))),
+        }
+    }
+}
+
+/// Renders a [`ParameterizedString`] using the attachments of a macro and the arguments of an invoker
+pub struct Renderer<'r> {
+    attachments: &'r [AttachmentInfo],
+    arguments: &'r [&'r str],
+    variables: &'r Variables,
+    /// The macros that inclusions are resolved from
+    includes: Option<&'r Includes>,
+    /// Names of the macros that are currently being rendered, outermost first
+    path: Vec<String>,
+}
+
+impl<'r> Renderer<'r> {
+    pub fn new(
+        attachments: &'r [AttachmentInfo],
+        arguments: &'r [&'r str],
+        variables: &'r Variables,
+    ) -> Self {
+        Self {
+            attachments,
+            arguments,
+            variables,
+            includes: None,
+            path: vec![],
+        }
+    }
+
+    /// Allow `{macro:name}` inclusions to be resolved from `includes`, which are loaded with
+    /// [`load_includes`], `name` is the macro that is being rendered
+    pub fn includes(mut self, includes: &'r Includes, name: impl Into<String>) -> Self {
+        self.includes = Some(includes);
+        self.path = vec![name.into()];
+        self
+    }
+
+    pub fn render(&self, template: &ParameterizedString) -> Result<String> {
+        template.check_attachments(self.attachments.len())?;
+        template.check_arguments(self.arguments)?;
+
+        let mut result = String::new();
+        let mut scope = vec![];
+
+        self.render_nodes(template, template.nodes(), &mut scope, &mut result)?;
+
+        Ok(result)
+    }
+
+    /// Render a list of nodes, `scope` contains the current item of every enclosing `{#each}` block
+    fn render_nodes<'s>(
+        &'s self,
+        template: &ParameterizedString,
+        nodes: &'s [Node],
+        scope: &mut Vec<(&'s str, &'s str)>,
+        result: &mut String,
+    ) -> Result<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => *result += text,
+                Node::Parameter(parameter, filters) => {
+                    let value = self.parameter(template, parameter, scope)?;
+
+                    *result += &filters
+                        .iter()
+                        .fold(value, |value, filter| Cow::Owned(filter.apply(&value)));
+                }
+                Node::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let nodes = if self.evaluate(template, condition, scope) {
+                        then
+                    } else {
+                        otherwise
+                    };
+
+                    self.render_nodes(template, nodes, scope, result)?;
+                }
+                Node::Each { name, body } => {
+                    for item in self.items(template, name) {
+                        scope.push((name, item));
+                        self.render_nodes(template, body, scope, result)?;
+                        scope.pop();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up the value of a placeholder
+    fn parameter<'s>(
+        &'s self,
+        template: &ParameterizedString,
+        parameter: &'s Parameter,
+        scope: &[(&str, &'r str)],
+    ) -> Result<Cow<'s, str>> {
+        let value = match *parameter {
+            Parameter::Attachment(i, property) => {
+                let attachment = &self.attachments[i];
+
+                match property {
+                    Property::Url => Cow::Borrowed(attachment.link.as_str()),
+                    Property::Filename => {
+                        Cow::Borrowed(attachment.filename.trim_start_matches("SPOILER_"))
+                    }
+                    // Attachments that were stored before sizes were recorded have no size to show
+                    Property::Size if attachment.size == 0 => Cow::Borrowed(""),
+                    Property::Size => Cow::Owned(format_size(attachment.size)),
+                    Property::ContentType => {
+                        Cow::Borrowed(attachment.content_type.as_deref().unwrap_or_default())
+                    }
+                    Property::Spoiler => Cow::Owned(attachment.spoiler().to_string()),
+                }
+            }
+            Parameter::Argument { name, default } => self
+                .argument(template, name, scope)
+                .unwrap_or(Cow::Borrowed(default.unwrap_or_default())),
+            Parameter::Variable(variable) => self.variables.get(variable).unwrap_or_default(),
+            Parameter::Include(name) => Cow::Owned(self.include(template, name)?),
+            Parameter::Random(options) => Cow::Borrowed(
+                options
+                    .split('|')
+                    .collect::<Vec<_>>()
+                    .choose(&mut rand::thread_rng())
+                    .copied()
+                    .unwrap_or_default(),
+            ),
+        };
+
+        Ok(value)
+    }
+
+    /// Render another macro in place, using the arguments of this macro that share a name with its
+    /// arguments
+    ///
+    /// Only the placeholder attachments of the included macro are used, any additional files are
+    /// left out.
+    fn include(&self, template: &ParameterizedString, name: &str) -> Result<String> {
+        let Some(includes) = self.includes else {
+            return Err(anyhow!("Macro inclusions cannot be resolved here"));
+        };
+
+        if self.path.iter().any(|parent| parent == name) {
+            return Err(anyhow!(
+                "Macro `.{name}` is included by itself: {} -> {name}",
+                self.path.join(" -> ")
+            ));
+        }
+
+        if self.path.len() > MAX_INCLUDE_DEPTH {
+            return Err(anyhow!(
+                "Macro inclusions may not be nested more than {MAX_INCLUDE_DEPTH} levels deep"
+            ));
+        }
+
+        let Some(cached) = includes.get(name) else {
+            return Err(anyhow!("Included macro `.{name}` does not exist"));
+        };
+
+        let included = cached.template()?;
+        let attachments = cached
+            .attachments
+            .iter()
+            .map(Attachment::info)
+            .collect::<Vec<_>>();
+
+        if attachments.len() < included.attachments() {
+            return Err(anyhow!(
+                "Included macro `.{name}` has more parameters than attachments"
+            ));
+        }
+
+        // Arguments are matched by name, stopping at the first one that this macro doesn't provide
+        let mut arguments = included
+            .argument_names()
+            .map_while(|argument| {
+                template
+                    .argument_index(argument)
+                    .and_then(|index| self.arguments.get(index).copied())
+            })
+            .collect::<Vec<_>>();
+
+        if let (Some((inner, _)), Some((outer, start))) = (included.variadic(), template.variadic())
+        {
+            if inner == outer && arguments.len() == included.argument_names().count() {
+                arguments.extend(self.arguments.get(start..).unwrap_or_default());
+            }
+        }
+
+        let mut path = self.path.clone();
+        path.push(name.to_string());
+
+        let renderer = Renderer {
+            attachments: &attachments,
+            arguments: &arguments,
+            variables: self.variables,
+            includes: self.includes,
+            path,
+        };
+
+        let mut result = String::new();
+        let mut scope = vec![];
+
+        renderer.render_nodes(included, included.nodes(), &mut scope, &mut result)?;
+
+        Ok(result)
+    }
+
+    /// Look up the value of an argument or built-in placeholder, variadic arguments are joined by
+    /// spaces outside of a loop
+    fn argument(
+        &self,
+        template: &ParameterizedString,
+        name: &str,
+        scope: &[(&str, &'r str)],
+    ) -> Option<Cow<'r, str>> {
+        if let Some(variable) = Variable::from_name(name) {
+            return self.variables.get(variable);
+        }
+
+        if let Some(&(_, item)) = scope.iter().rev().find(|(variable, _)| *variable == name) {
+            return Some(Cow::Borrowed(item));
+        }
+
+        if template
+            .variadic()
+            .is_some_and(|(variadic, _)| variadic == name)
+        {
+            let items = self.items(template, name);
+
+            return (!items.is_empty()).then(|| Cow::Owned(items.join(" ")));
+        }
+
+        let index = template.argument_index(name)?;
+
+        self.arguments
+            .get(index)
+            .map(|argument| Cow::Borrowed(*argument))
+    }
+
+    /// All values of the argument that is looped over
+    fn items(&self, template: &ParameterizedString, name: &str) -> &'r [&'r str] {
+        match template.variadic() {
+            Some((variadic, start)) if variadic == name => {
+                self.arguments.get(start..).unwrap_or_default()
+            }
+            _ => match template.argument_index(name) {
+                Some(index) => self.arguments.get(index..index + 1).unwrap_or_default(),
+                None => &[],
+            },
+        }
+    }
+
+    /// A condition holds if the argument was supplied and, if a value is given, matches that value
+    fn evaluate(
+        &self,
+        template: &ParameterizedString,
+        condition: &Condition,
+        scope: &[(&str, &'r str)],
+    ) -> bool {
+        let Some(argument) = self.argument(template, condition.name, scope) else {
+            return false;
+        };
+
+        match condition.value {
+            Some(value) => argument.eq_ignore_ascii_case(value),
+            None => !argument.is_empty(),
+        }
+    }
+}
+
+/// Format a file size the way Discord does, e.g. `12.5 MB`
+fn format_size(size: i32) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} {}", UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit]).replace(".0 ", " ")
+    }
+}
@@ -0,0 +1,210 @@
+/// The maximum amount of characters in a Discord message
+pub const MESSAGE_LIMIT: usize = 2000;
+
+/// The longest word after an opening fence that is recognized as the language of a code block
+const LANGUAGE_LIMIT: usize = 20;
+
+/// Split content into messages that fit within Discord's character limit
+///
+/// Content is preferably split between paragraphs, then between lines, then between words. Code
+/// blocks that are cut in half are closed at the end of one message and reopened at the start of
+/// the next.
+pub fn split_message(content: &str) -> Vec<String> {
+    let mut messages = vec![];
+    let mut rest = content.to_string();
+
+    loop {
+        let length = rest.chars().count();
+
+        if length <= MESSAGE_LIMIT {
+            break;
+        }
+
+        // Leave room to close a code block that is still open
+        let window_end = rest
+            .char_indices()
+            .nth(MESSAGE_LIMIT - 4)
+            .map_or(rest.len(), |(index, _)| index);
+        let window = &rest[..window_end];
+
+        // Reopening a code block adds to the next message, so a break is only used if the rest
+        // still gets shorter. Splitting at the end of the window always does, as the fence and
+        // language are much shorter than a message.
+        let (message, next) = [
+            find_break(window, "\n\n", window.len() / 2),
+            find_break(window, "\n", window.len() / 2),
+            find_break(window, " ", 1),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(end, start)| split_at(&rest, end, start))
+        .find(|(_, next)| next.chars().count() < length)
+        .unwrap_or_else(|| split_at(&rest, window_end, window_end));
+
+        messages.push(message);
+        rest = next;
+    }
+
+    if !rest.is_empty() || messages.is_empty() {
+        messages.push(rest);
+    }
+
+    messages
+}
+
+/// Split `content` into a message that ends at `end` and the rest that starts at `start`, closing
+/// and reopening a code block that is cut in half
+fn split_at(content: &str, end: usize, start: usize) -> (String, String) {
+    let mut message = content[..end].to_string();
+    let mut next = content[start..].to_string();
+
+    if let Some(language) = open_code_block(&message) {
+        next = format!("```{language}\n{next}");
+        message += "\n```";
+    }
+
+    (message, next)
+}
+
+/// Find the last occurrence of `separator` at or after `min`, returning where the current message
+/// ends and where the next one starts
+fn find_break(window: &str, separator: &str, min: usize) -> Option<(usize, usize)> {
+    window
+        .rfind(separator)
+        .filter(|&index| index >= min)
+        .map(|index| (index, index + separator.len()))
+}
+
+/// Returns the language of the last code block if it hasn't been closed
+fn open_code_block(content: &str) -> Option<&str> {
+    let mut language = None;
+    let mut rest = content;
+
+    while let Some(index) = rest.find("```") {
+        rest = &rest[index + 3..];
+
+        language = match language {
+            Some(_) => None,
+            None => {
+                // The language is only recognized if it's a single short word that is directly
+                // followed by a newline
+                let line = rest.split('\n').next().unwrap_or_default();
+
+                Some(
+                    if line.chars().count() <= LANGUAGE_LIMIT
+                        && line
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || "+-#".contains(c))
+                    {
+                        line
+                    } else {
+                        ""
+                    },
+                )
+            }
+        };
+    }
+
+    language
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lengths(messages: &[String]) -> Vec<usize> {
+        messages
+            .iter()
+            .map(|message| message.chars().count())
+            .collect()
+    }
+
+    #[test]
+    fn short_content() {
+        assert_eq!(split_message("Hello"), ["Hello"]);
+        assert_eq!(split_message(""), [""]);
+        assert_eq!(split_message(&"a".repeat(MESSAGE_LIMIT)).len(), 1);
+    }
+
+    #[test]
+    fn split_between_paragraphs() {
+        let first = format!("{}\n{}", "a".repeat(1000), "b".repeat(500));
+        let second = "c".repeat(1000);
+        let messages = split_message(&format!("{first}\n\n{second}"));
+
+        assert_eq!(messages, [first, second]);
+    }
+
+    #[test]
+    fn split_between_lines() {
+        let first = format!("{} {}", "a".repeat(1000), "b".repeat(500));
+        let second = "c".repeat(1000);
+        let messages = split_message(&format!("{first}\n{second}"));
+
+        assert_eq!(messages, [first, second]);
+    }
+
+    #[test]
+    fn split_between_words() {
+        let words = vec!["word"; 500].join(" ");
+        let messages = split_message(&words);
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].ends_with("word"));
+        assert!(messages[1].starts_with("word"));
+        assert_eq!(messages.join(" "), words);
+    }
+
+    #[test]
+    fn hard_split() {
+        let content = "ä".repeat(5000);
+        let messages = split_message(&content);
+
+        assert_eq!(lengths(&messages), [1996, 1996, 1008]);
+        assert_eq!(messages.concat(), content);
+    }
+
+    #[test]
+    fn reopen_code_block() {
+        let code = vec!["let x = 1;"; 300].join("\n");
+        let messages = split_message(&format!("Example:\n```rust\n{code}\n```"));
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("Example:\n```rust\n"));
+        assert!(messages[0].ends_with("let x = 1;\n```"));
+        assert!(messages[1].starts_with("```rust\nlet x = 1;"));
+        assert!(messages[1].ends_with("\n```"));
+        assert!(lengths(&messages)
+            .into_iter()
+            .all(|length| length <= MESSAGE_LIMIT));
+    }
+
+    #[test]
+    fn long_language_is_not_reopened() {
+        let messages = split_message(&format!("```{}", "a".repeat(3000)));
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].starts_with("```\naaa"));
+
+        let messages = split_message(&format!("```{}\n{}", "a".repeat(1200), "b".repeat(2000)));
+
+        assert_eq!(messages.len(), 3);
+        assert!(lengths(&messages)
+            .into_iter()
+            .all(|length| length <= MESSAGE_LIMIT));
+    }
+
+    #[test]
+    fn reopening_always_makes_progress() {
+        for content in [
+            format!("``` {}", "a".repeat(3000)),
+            format!("```rust {}", "a".repeat(3000)),
+            format!("```rust\n{}", "a ".repeat(3000)),
+            format!("{}```", "\n".repeat(3000)),
+        ] {
+            assert!(lengths(&split_message(&content))
+                .into_iter()
+                .all(|length| length <= MESSAGE_LIMIT));
+        }
+    }
+}